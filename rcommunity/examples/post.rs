@@ -0,0 +1,164 @@
+use rcommunity::{
+    community, error::Result, store::memory::MemoryStore, Enumerable, ItemType, Numerical, Once,
+    ReactionType, Unique, UserType, ID,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, ID, UserType, Serialize, Deserialize)]
+struct User(String);
+
+#[derive(Clone, ID, ItemType, Serialize, Deserialize)]
+struct Item(String);
+
+#[derive(Clone, ID, ItemType, Serialize, Deserialize)]
+struct Post(String);
+
+#[derive(Clone, Debug, ReactionType, Numerical, Enumerable, Once, Serialize, Deserialize)]
+#[numerical(Item = i32)]
+enum Vote {
+    Upvote = 1,
+    Downvote = -1,
+}
+
+#[derive(Clone, Debug, ReactionType, Unique, Serialize, Deserialize)]
+struct Tag(String);
+
+#[derive(Clone, Debug, ID, ReactionType, ItemType, Serialize, Deserialize)]
+struct Comment(String);
+
+#[derive(Clone, Debug, ReactionType, Numerical, Enumerable, Once, Serialize, Deserialize)]
+#[numerical(Item = i32)]
+enum Rate {
+    R5 = 5,
+    R4 = 4,
+    R3 = 3,
+    R2 = 2,
+    R1 = 1,
+}
+
+#[derive(
+    Clone, Debug, ID, ReactionType, Numerical, Enumerable, ItemType, Serialize, Deserialize,
+)]
+#[numerical(Item = i32)]
+struct Review(Rate, String);
+
+community!(
+    struct Community,
+    Vote: [User] => [Post, Comment, Review],
+    Tag: [User] => [Post],
+    Comment: [User] => [Post, Comment],
+    Review: [User] => [Item],
+);
+
+impl From<&str> for User {
+    fn from(id: &str) -> Self {
+        User(id.to_string())
+    }
+}
+
+impl From<&str> for Item {
+    fn from(id: &str) -> Self {
+        Item(id.to_string())
+    }
+}
+
+impl From<&str> for Post {
+    fn from(id: &str) -> Self {
+        Post(id.to_string())
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut community = Community::new(MemoryStore::default());
+
+    let uid = "1000";
+    let pid = "2001";
+
+    community
+        .user()
+        .get(uid)
+        .post()
+        .get(pid)
+        .vote()
+        .add(Vote::Upvote)
+        .await?;
+
+    let vote = community
+        .user()
+        .get(uid)
+        .post()
+        .get(pid)
+        .vote()
+        .get()
+        .await?; // -> Option<Vote>
+    println!("vote of user {uid} on post {pid}: {vote:?}");
+
+    let votes = community.post().get(pid).vote().received().sum().await?;
+    println!("votes on post {pid}: {votes}");
+
+    let given = community
+        .user()
+        .get(uid)
+        .post()
+        .vote()
+        .given()
+        .sum()
+        .await?;
+    println!("votes given by user {uid} on posts: {given}");
+
+    let mut user = community.user().get(uid);
+    user.post()
+        .get(pid)
+        .tag()
+        .add(Tag("rust".to_string()))
+        .await?;
+    let comment = Comment("hello".to_string());
+    user.post().get(pid).comment().add(comment.clone()).await?;
+    user.comment()
+        .get(comment.clone())
+        .vote()
+        .add(Vote::Downvote)
+        .await?;
+
+    let votes = community
+        .comment()
+        .get(comment)
+        .vote()
+        .received()
+        .sum()
+        .await?;
+    println!("votes on the comment: {votes}");
+
+    let review = Review(Rate::R5, "great".to_string());
+    community
+        .user()
+        .get(uid)
+        .item()
+        .get("3001")
+        .review()
+        .add(review.clone())
+        .await?;
+    community
+        .user()
+        .get("1001")
+        .review()
+        .get(review)
+        .vote()
+        .add(Vote::Upvote)
+        .await?;
+
+    let mut item = community.item().get("3001");
+    let rating = item.review().received().sum().await?;
+    let count = item.review().received().count().await?;
+    println!("rating of item 3001: {rating} in {count} reviews");
+
+    let upvotes = community
+        .post()
+        .get(pid)
+        .vote()
+        .list_by_value(Vote::Upvote)
+        .await?;
+    println!("upvotes on post {pid}: {upvotes:?}");
+    Ok(())
+}
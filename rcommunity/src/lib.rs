@@ -1,2 +1,7 @@
-pub use rcommunity_core::{ReactionType, UserType, ID};
-pub use rcommunity_macros::{ReactionType, UserType, ID};
+pub use rcommunity_core::{
+    client, error, store, Enumerable, ItemType, Numerical, NumericalValue, Once, OnceMode,
+    ReactionType, UserType, ID,
+};
+pub use rcommunity_macros::{
+    community, Enumerable, ItemType, Numerical, Once, ReactionType, Unique, UserType, ID,
+};
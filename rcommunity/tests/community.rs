@@ -0,0 +1,177 @@
+use rcommunity::{community, store::memory::MemoryStore, ReactionType, UserType, ID};
+use rcommunity_core::{ops::Aggregate, Enumerable, ItemType, Numerical, Once};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, ID, UserType, Serialize, Deserialize)]
+struct User(String);
+
+#[derive(Clone, Debug, ID, Serialize, Deserialize)]
+struct Post(String);
+impl ItemType for Post {}
+
+#[derive(Clone, Debug, ID, Serialize, Deserialize)]
+struct Comment(String);
+impl ItemType for Comment {}
+
+#[derive(Clone, Debug, Eq, PartialEq, ReactionType, Serialize, Deserialize)]
+struct Vote(i32);
+impl Once for Vote {}
+impl Enumerable for Vote {}
+impl Numerical for Vote {
+    type Item = i32;
+    fn value(&self) -> i32 {
+        self.0
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, ReactionType, Serialize, Deserialize)]
+struct Reply(String);
+
+community!(
+    struct Community,
+    Vote: [User] => [Post, Comment],
+    Reply: [User] => [Post],
+);
+
+impl From<&str> for User {
+    fn from(id: &str) -> Self {
+        User(id.to_string())
+    }
+}
+
+impl From<&str> for Post {
+    fn from(id: &str) -> Self {
+        Post(id.to_string())
+    }
+}
+
+#[tokio::test]
+async fn test_community() {
+    let mut community = Community::new(MemoryStore::default());
+
+    let r = community
+        .user()
+        .get("1000")
+        .post()
+        .get("2000")
+        .vote()
+        .react(Vote(1))
+        .await
+        .unwrap();
+    assert_eq!(r.replaced, None);
+    community
+        .user()
+        .get("1001")
+        .post()
+        .get("2000")
+        .vote()
+        .add(Vote(1))
+        .await
+        .unwrap();
+    community
+        .user()
+        .get("1000")
+        .comment()
+        .get(Comment("3000".to_string()))
+        .vote()
+        .react(Vote(-1))
+        .await
+        .unwrap();
+
+    let mut post = community.post().get("2000");
+    assert_eq!(
+        post.vote().aggregate().await.unwrap(),
+        Aggregate { sum: 2, count: 2 }
+    );
+    assert_eq!(post.vote().received().sum().await.unwrap(), 2);
+    assert_eq!(post.vote().count_by_value(Vote(1)).await.unwrap(), 2);
+    let mut user = community.user().get("1000");
+    assert_eq!(user.post().vote().given().sum().await.unwrap(), 1);
+    assert_eq!(user.comment().vote().given().sum().await.unwrap(), -1);
+
+    let mut user = community.user().get("1000");
+    let mut reply = user.post().get("2000");
+    reply
+        .reply()
+        .react(Reply("hello".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(reply.vote().clear().await.unwrap(), Some(Vote(1)));
+    assert_eq!(
+        community
+            .post()
+            .get("2000")
+            .vote()
+            .aggregate()
+            .await
+            .unwrap(),
+        Aggregate { sum: 1, count: 1 }
+    );
+}
+
+mod legacy {
+    use rcommunity::{ItemType, Once, ReactionType, ID};
+    use serde::{Deserialize, Serialize};
+
+    // tagged as its former name, which is now taken by another type
+    #[derive(Clone, Debug, Eq, PartialEq, ReactionType, Once, Serialize, Deserialize)]
+    #[rcommunity(tag = "Vote")]
+    pub struct Upvote(pub u32);
+
+    // tagged as the name of another item type
+    #[derive(Clone, Debug, ID, ItemType, Serialize, Deserialize)]
+    #[rcommunity(tag = "Post")]
+    pub struct Thread(pub String);
+}
+
+community!(
+    struct Legacy,
+    legacy::Upvote: [User] => [Comment],
+);
+
+community!(
+    struct Conflicting,
+    Vote: [User] => [Post],
+    legacy::Upvote: [User] => [Comment],
+);
+
+community!(
+    struct ConflictingItems,
+    Reply: [User] => [Post, legacy::Thread],
+);
+
+#[tokio::test]
+async fn test_community_legacy() {
+    let mut community = Legacy::new(MemoryStore::default());
+    let mut user = community.user().get("1000");
+    let mut comment = user.comment().get(Comment("3000".to_string()));
+    comment.upvote().react(legacy::Upvote(2)).await.unwrap();
+    let r = comment.upvote().react(legacy::Upvote(3)).await.unwrap();
+    assert_eq!(r.replaced, Some(legacy::Upvote(2)));
+    assert_eq!(
+        comment.upvote().clear().await.unwrap(),
+        Some(legacy::Upvote(3))
+    );
+}
+
+#[test]
+fn test_community_tags() {
+    let tags = Community::<MemoryStore>::tags().unwrap();
+    assert_eq!(tags.get("Vote"), Some(std::any::type_name::<Vote>()));
+    assert_eq!(tags.get("User"), Some(std::any::type_name::<User>()));
+    assert_eq!(tags.get("Post"), Some(std::any::type_name::<Post>()));
+    assert!(Conflicting::<MemoryStore>::tags().is_err());
+    assert!(ConflictingItems::<MemoryStore>::tags().is_err());
+}
+
+#[test]
+#[should_panic(expected = "Type tag `Vote`")]
+fn test_community_conflicting_tags() {
+    Conflicting::new(MemoryStore::default());
+}
+
+#[test]
+#[should_panic(expected = "Type tag `Post`")]
+fn test_community_conflicting_item_tags() {
+    ConflictingItems::new(MemoryStore::default());
+}
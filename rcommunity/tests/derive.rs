@@ -1,5 +1,142 @@
-use rcommunity::{UserType, ID};
-use serde::Serialize;
+use rcommunity::{
+    Enumerable, ItemType, Numerical, Once, OnceMode, ReactionType, Unique, UserType, ID,
+};
+use rcommunity_core::{
+    client::{ItemReactionClient, UserItemUnboundedReactionClient},
+    ops::Aggregate,
+    store::{format::typename, memory::MemoryStore, Store, Transaction},
+};
+use serde::{Deserialize, Serialize};
 
 #[derive(Clone, ID, UserType, Serialize)]
 struct User(String);
+
+#[derive(Clone, ID, ItemType, Serialize)]
+#[rcommunity(tag = "acme.item")]
+struct Item(String);
+
+#[derive(Clone, ReactionType, Once, Enumerable, Numerical, Serialize)]
+#[numerical(Item = i32)]
+enum Vote {
+    Up = 1,
+    Down = -1,
+}
+
+#[derive(Clone, ReactionType, Once, Numerical, Serialize)]
+#[once(reject)]
+struct Rating(u8);
+
+#[derive(Clone, ReactionType, Numerical, Serialize)]
+#[numerical(Item = i64)]
+struct Tip {
+    amount: u32,
+}
+
+#[derive(Clone, ReactionType, Unique, Serialize)]
+struct Tag(String);
+
+fn assert_user_type<T: UserType>(_: &T) {}
+fn assert_item_type<T: ItemType>(_: &T) {}
+fn assert_enumerable<T: Enumerable>(_: &T) {}
+fn assert_unique<T: ReactionType + ID>(_: &T) {}
+fn once_mode<T: Once>(_: &T) -> OnceMode {
+    T::MODE
+}
+
+#[test]
+fn test_derive() {
+    assert_user_type(&User("1000".to_string()));
+    assert_item_type(&Item("2000".to_string()));
+    assert_enumerable(&Vote::Up);
+    assert_unique(&Tag("rust".to_string()));
+}
+
+#[test]
+fn test_derive_once() {
+    assert_eq!(once_mode(&Vote::Up), OnceMode::Replace);
+    assert_eq!(once_mode(&Rating(5)), OnceMode::Reject);
+}
+
+#[test]
+fn test_derive_numerical() {
+    assert_eq!(Vote::Up.value(), 1);
+    assert_eq!(Vote::Down.value(), -1);
+    assert_eq!(Rating(5).value(), 5u8);
+    assert_eq!(Tip { amount: 100 }.value(), 100i64);
+}
+
+#[test]
+fn test_derive_tag() {
+    assert_eq!(typename::<User>(), "User");
+    assert_eq!(typename::<Item>(), "acme.item");
+}
+
+mod before {
+    use rcommunity::{ItemType, UserType, ID};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, ID, UserType, Serialize, Deserialize)]
+    #[rcommunity(tag = "acme.user")]
+    pub struct Account(pub String);
+
+    #[derive(Clone, ID, ItemType, Serialize, Deserialize)]
+    #[rcommunity(tag = "acme.post")]
+    pub struct Article {
+        pub id: u64,
+    }
+}
+
+// renamed types keeping the tags of those in `before`
+#[derive(Clone, ID, UserType, Serialize, Deserialize)]
+#[rcommunity(tag = "acme.user")]
+struct Member(String);
+
+#[derive(Clone, ID, ItemType, Serialize, Deserialize)]
+#[rcommunity(tag = "acme.post")]
+struct Post {
+    id: u64,
+}
+
+#[derive(Clone, ReactionType, Once, Numerical, Serialize, Deserialize)]
+#[numerical(Item = i32)]
+struct Score(i32);
+
+#[tokio::test]
+async fn test_derive_tag_keys() {
+    let mut store = MemoryStore::default();
+    let mut client = UserItemUnboundedReactionClient::<_, _, _, Score>::new(
+        &mut store,
+        before::Account("1000".to_string()),
+        before::Article { id: 2000 },
+    );
+    client.react(Score(3)).await.unwrap();
+
+    // keys are written with tags instead of type names
+    let txn = store.begin_txn().await.unwrap();
+    let keys: Vec<_> = txn
+        .scan_keys(b"", &[0xFF], usize::MAX)
+        .await
+        .unwrap()
+        .collect();
+    drop(txn);
+    assert!(keys.iter().any(|k| k.windows(9).any(|w| w == b"acme.post")));
+    for name in [&b"Account"[..], b"Article"] {
+        assert!(!keys
+            .iter()
+            .any(|k| k.windows(name.len()).any(|w| w == name)));
+    }
+
+    // so that reactions are found through the renamed types
+    let mut client = ItemReactionClient::<_, _, Score>::new(&mut store, Post { id: 2000 });
+    assert_eq!(
+        client.aggregate().await.unwrap(),
+        Aggregate { sum: 3, count: 1 }
+    );
+    let mut client = UserItemUnboundedReactionClient::<_, _, _, Score>::new(
+        &mut store,
+        Member("1000".to_string()),
+        Post { id: 2000 },
+    );
+    let r = client.react(Score(5)).await.unwrap();
+    assert!(r.replaced.is_some());
+}
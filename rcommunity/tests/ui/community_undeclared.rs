@@ -0,0 +1,36 @@
+use rcommunity::{community, store::memory::MemoryStore, ReactionType, UserType, ID};
+use rcommunity_core::ItemType;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, ID, UserType, Serialize, Deserialize)]
+struct User(String);
+
+#[derive(Clone, ID, Serialize, Deserialize)]
+struct Post(String);
+impl ItemType for Post {}
+
+#[derive(Clone, ID, Serialize, Deserialize)]
+struct Comment(String);
+impl ItemType for Comment {}
+
+#[derive(Clone, ReactionType, Serialize, Deserialize)]
+struct Vote(i32);
+
+#[derive(Clone, ReactionType, Serialize, Deserialize)]
+struct Reply(String);
+
+community!(
+    struct Community,
+    Vote: [User] => [Post, Comment],
+    Reply: [User] => [Post],
+);
+
+fn main() {
+    let mut community = Community::new(MemoryStore::default());
+    let _ = community
+        .user()
+        .get(User("1000".to_string()))
+        .comment()
+        .get(Comment("3000".to_string()))
+        .reply();
+}
@@ -0,0 +1,8 @@
+use rcommunity::{Numerical, ReactionType};
+use serde::Serialize;
+
+#[derive(Clone, ReactionType, Numerical, Serialize)]
+#[numerical(Value = i32)]
+struct Score(i32);
+
+fn main() {}
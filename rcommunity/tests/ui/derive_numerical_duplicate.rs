@@ -0,0 +1,12 @@
+use rcommunity::{Numerical, ReactionType};
+use serde::Serialize;
+
+#[derive(Clone, ReactionType, Numerical, Serialize)]
+#[numerical(Item = i32)]
+#[numerical(Item = i64)]
+enum Vote {
+    Upvote = 1,
+    Downvote = -1,
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use rcommunity::{Numerical, ReactionType};
+use serde::Serialize;
+
+#[derive(Clone, ReactionType, Numerical, Serialize)]
+enum Vote {
+    Up = 1,
+    Down = -1,
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+use rcommunity::{Numerical, ReactionType};
+use serde::Serialize;
+
+#[derive(Clone, ReactionType, Numerical, Serialize)]
+struct Score;
+
+fn main() {}
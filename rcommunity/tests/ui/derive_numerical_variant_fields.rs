@@ -0,0 +1,11 @@
+use rcommunity::{Numerical, ReactionType};
+use serde::Serialize;
+
+#[derive(Clone, ReactionType, Numerical, Serialize)]
+#[numerical(Item = i32)]
+enum Vote {
+    Up,
+    Custom(i32),
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use rcommunity::{Once, ReactionType};
+use serde::Serialize;
+
+#[derive(Clone, ReactionType, Once, Serialize)]
+#[once(ignore)]
+struct Vote(i32);
+
+fn main() {}
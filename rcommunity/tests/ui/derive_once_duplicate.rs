@@ -0,0 +1,9 @@
+use rcommunity::{Once, ReactionType};
+use serde::Serialize;
+
+#[derive(Clone, ReactionType, Once, Serialize)]
+#[once(reject)]
+#[once(replace)]
+struct Vote(i32);
+
+fn main() {}
@@ -0,0 +1,12 @@
+use rcommunity::{ReactionType, UserType, ID};
+use serde::Serialize;
+
+#[derive(Clone, ID, UserType, Serialize)]
+#[rcommunity(name = "user")]
+struct User(String);
+
+#[derive(Clone, ReactionType, Serialize)]
+#[rcommunity(tag = "")]
+struct Vote(i32);
+
+fn main() {}
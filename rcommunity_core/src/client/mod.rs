@@ -5,12 +5,30 @@ use std::marker::PhantomData;
 use serde::de::DeserializeOwned;
 
 use crate::{
-    error::Result,
-    markers::{ItemType, ReactionType, UserType},
-    ops::Reactor,
-    store::{Store, Transaction},
+    error::{Error, Result},
+    markers::{Enumerable, ItemType, Numerical, Once, ReactionType, UserType},
+    ops::{
+        Aggregate, EnumIndexLookup, NumericalAggregateLookup, ReactionInfo, ReactionInfoOnce,
+        Reactor,
+    },
+    store::{
+        format::{to_key, typename},
+        Direction, Store, Transaction,
+    },
 };
 
+#[cfg(test)]
+pub(crate) mod tests;
+
+/// Result of creating a reaction.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Reacted<TR: ReactionType> {
+    /// ID of the new reaction.
+    pub rid: String,
+    /// Reaction replaced by the new one, for [`Once`] reactions.
+    pub replaced: Option<TR>,
+}
+
 #[derive(Debug)]
 pub struct UserItemUnboundedReactionClient<
     'store,
@@ -25,24 +43,341 @@ pub struct UserItemUnboundedReactionClient<
     reaction_type: PhantomData<TR>,
 }
 
+impl<'store, TS: Store, TU: UserType, TI: ItemType, TR: ReactionType>
+    UserItemUnboundedReactionClient<'store, TS, TU, TI, TR>
+{
+    /// Create a client for reactions of type `TR` from `user` to `item`.
+    pub fn new(store: &'store mut TS, user: TU, item: TI) -> Self {
+        Self {
+            store,
+            user,
+            item,
+            reaction_type: PhantomData,
+        }
+    }
+}
+
 impl<
-        'store,
         TS: Store,
         TU: UserType + DeserializeOwned,
         TI: ItemType + DeserializeOwned,
         TR: ReactionType + DeserializeOwned,
-    > UserItemUnboundedReactionClient<'store, TS, TU, TI, TR>
+    > UserItemUnboundedReactionClient<'_, TS, TU, TI, TR>
 {
     /// Create a new reaction.
     ///
     /// # Errors
+    /// Will return error when internal store failed, or when the reaction
+    /// conflicts with an existing one. Concurrent reactions conflicting in
+    /// store are retried, see [`Store::run_in_txn`].
+    pub async fn react(&mut self, reaction: impl Into<TR>) -> Result<Reacted<TR>> {
+        let r: TR = reaction.into();
+        // time-ordered, so that reactions are indexed in creation order
+        let rid = uuid::Uuid::now_v7().to_string(); // TODO: keep Uuid type
+        let (r, rid_ref, user, item) = (&r, &rid, &self.user, &self.item);
+        let replaced = self
+            .store
+            .run_in_txn(move |mut txn| async move {
+                let result = r.react(&mut txn, rid_ref, user, item).await;
+                (txn, result)
+            })
+            .await?;
+        Ok(Reacted { rid, replaced })
+    }
+
+    /// Create a new reaction, same as [`react`](Self::react).
+    ///
+    /// # Errors
+    /// Will return error as [`react`](Self::react) does.
+    pub async fn add(&mut self, reaction: impl Into<TR>) -> Result<Reacted<TR>> {
+        self.react(reaction).await
+    }
+
+    /// Remove a reaction of this user-item pair by ID. Returns the removed
+    /// reaction.
+    ///
+    /// # Errors
+    /// Will return [`Error::NotFound`] when no such reaction exists for this
+    /// user-item pair, or error when internal store failed.
+    pub async fn unreact(&mut self, rid: &str) -> Result<TR> {
+        let mut txn = self.store.begin_txn().await?;
+        let result = self.unreact_in(&mut txn, rid).await;
+        finish(txn, result).await
+    }
+
+    /// Remove all reactions of this user-item pair. Returns IDs of the removed
+    /// reactions.
+    ///
+    /// # Errors
     /// Will return error when internal store failed.
-    pub async fn react(&mut self, reaction: impl Into<TR>) -> Result<String> {
+    pub async fn unreact_all(&mut self) -> Result<Vec<String>> {
+        let mut txn = self.store.begin_txn().await?;
+        let result = self.unreact_all_in(&mut txn).await;
+        finish(txn, result).await
+    }
+
+    async fn unreact_in(&self, txn: &mut TS::Transaction, rid: &str) -> Result<TR> {
+        let r = TR::get_reaction_by_id::<TU, TI>(txn, rid).await?;
+        // only reactions of this user-item pair can be removed
+        if to_key(&r.user)? != to_key(&self.user)? || to_key(&r.item)? != to_key(&self.item)? {
+            return Err(Error::NotFound {
+                reaction_type: typename::<TR>(),
+                rid: Some(rid.to_string()),
+            });
+        }
+        TR::dereact::<TU, TI>(txn, rid).await
+    }
+
+    async fn unreact_all_in(&self, txn: &mut TS::Transaction) -> Result<Vec<String>> {
+        let rids = TR::get_rids(txn, &self.user, &self.item).await?;
+        for rid in &rids {
+            TR::dereact::<TU, TI>(txn, rid).await?;
+        }
+        Ok(rids)
+    }
+}
+
+impl<
+        TS: Store,
+        TU: UserType + DeserializeOwned,
+        TI: ItemType + DeserializeOwned,
+        TR: ReactionType + DeserializeOwned + Once,
+    > UserItemUnboundedReactionClient<'_, TS, TU, TI, TR>
+{
+    /// Get the reaction of this user-item pair, if any.
+    ///
+    /// # Errors
+    /// Will return error when internal store failed.
+    pub async fn get(&mut self) -> Result<Option<TR>> {
+        let mut txn = self.store.begin_txn().await?;
+        let result = self.get_in(&mut txn).await;
+        finish(txn, result).await
+    }
+
+    /// Remove the reaction of this user-item pair, if any. Returns the removed
+    /// reaction.
+    ///
+    /// # Errors
+    /// Will return error when internal store failed.
+    pub async fn clear(&mut self) -> Result<Option<TR>> {
+        let mut txn = self.store.begin_txn().await?;
+        let result = self.clear_in(&mut txn).await;
+        finish(txn, result).await
+    }
+
+    async fn get_in(&self, txn: &mut TS::Transaction) -> Result<Option<TR>> {
+        let rid = match TR::get_rid(txn, &self.user, &self.item).await {
+            Ok(rid) => rid,
+            Err(Error::NotFound { .. }) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let info = TR::get_reaction_by_id::<TU, TI>(txn, &rid).await?;
+        Ok(Some(info.reaction))
+    }
+
+    async fn clear_in(&self, txn: &mut TS::Transaction) -> Result<Option<TR>> {
+        let rid = match TR::get_rid(txn, &self.user, &self.item).await {
+            Ok(rid) => rid,
+            Err(Error::NotFound { .. }) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        Ok(Some(TR::dereact::<TU, TI>(txn, &rid).await?))
+    }
+}
+
+/// Client for querying reactions of type `TR` received by an item.
+#[derive(Debug)]
+pub struct ItemReactionClient<'store, TS: Store, TI: ItemType, TR: ReactionType> {
+    store: &'store mut TS,
+    item: TI,
+    reaction_type: PhantomData<TR>,
+}
+
+impl<'store, TS: Store, TI: ItemType, TR: ReactionType> ItemReactionClient<'store, TS, TI, TR> {
+    /// Create a client for reactions of type `TR` received by `item`.
+    pub fn new(store: &'store mut TS, item: TI) -> Self {
+        Self {
+            store,
+            item,
+            reaction_type: PhantomData,
+        }
+    }
+}
+
+impl<TS: Store, TI: ItemType, TR: ReactionType + Numerical> ItemReactionClient<'_, TS, TI, TR> {
+    /// Sum and count of reactions received by this item.
+    ///
+    /// # Errors
+    /// Will return error when internal store failed.
+    pub async fn aggregate(&mut self) -> Result<Aggregate<TR::Item>> {
+        let mut txn = self.store.begin_txn().await?;
+        let result = TR::get_item_aggregate(&mut txn, &self.item).await;
+        finish(txn, result).await
+    }
+
+    /// Totals of reactions received by this item, as in
+    /// `client.received().sum()`.
+    pub fn received(&mut self) -> Totals<'_, Self> {
+        Totals { client: self }
+    }
+}
+
+impl<TS, TI, TR> ItemReactionClient<'_, TS, TI, TR>
+where
+    TS: Store,
+    TI: ItemType + DeserializeOwned,
+    TR: ReactionType + DeserializeOwned + Enumerable,
+{
+    /// List IDs of reactions with the given value received by this item.
+    ///
+    /// # Errors
+    /// Will return error when internal store failed.
+    pub async fn list_by_value(&mut self, reaction: impl Into<TR>) -> Result<Vec<String>> {
+        let r: TR = reaction.into();
+        let mut txn = self.store.begin_txn().await?;
+        let result = r.list_by_value(&mut txn, &self.item).await;
+        finish(txn, result).await
+    }
+
+    /// List a page of at most `limit` IDs of reactions with the given value
+    /// received by this item, continuing from reaction `cursor` exclusively in
+    /// `direction`. The last ID is the cursor of the next page. Reaction IDs
+    /// are ordered by creation time, so [`Direction::Backward`] lists the most
+    /// recent reactions first.
+    ///
+    /// # Errors
+    /// Will return error when internal store failed, or does not support
+    /// scanning in `direction`.
+    pub async fn list_by_value_page(
+        &mut self,
+        reaction: impl Into<TR>,
+        cursor: Option<&str>,
+        direction: Direction,
+        limit: usize,
+    ) -> Result<Vec<String>> {
+        let r: TR = reaction.into();
+        let mut txn = self.store.begin_txn().await?;
+        let result = r
+            .list_by_value_page(&mut txn, &self.item, cursor, direction, limit)
+            .await;
+        finish(txn, result).await
+    }
+
+    /// Count reactions with the given value received by this item.
+    ///
+    /// # Errors
+    /// Will return error when internal store failed.
+    pub async fn count_by_value(&mut self, reaction: impl Into<TR>) -> Result<usize> {
         let r: TR = reaction.into();
         let mut txn = self.store.begin_txn().await?;
-        let rid = uuid::Uuid::new_v4().to_string(); // TODO: keep Uuid type
-        r.react(&mut txn, &rid, &self.user, &self.item).await?;
-        txn.commit().await?;
-        Ok(rid)
+        let result = r.count_by_value(&mut txn, &self.item).await;
+        finish(txn, result).await
+    }
+}
+
+/// Client for querying reactions of type `TR` given by a user to items of type
+/// `TI`.
+#[derive(Debug)]
+pub struct UserReactionClient<'store, TS: Store, TU: UserType, TI: ItemType, TR: ReactionType> {
+    store: &'store mut TS,
+    user: TU,
+    item_type: PhantomData<TI>,
+    reaction_type: PhantomData<TR>,
+}
+
+impl<'store, TS: Store, TU: UserType, TI: ItemType, TR: ReactionType>
+    UserReactionClient<'store, TS, TU, TI, TR>
+{
+    /// Create a client for reactions of type `TR` given by `user` to items of
+    /// type `TI`.
+    pub fn new(store: &'store mut TS, user: TU) -> Self {
+        Self {
+            store,
+            user,
+            item_type: PhantomData,
+            reaction_type: PhantomData,
+        }
+    }
+}
+
+impl<TS: Store, TU: UserType, TI: ItemType, TR: ReactionType + Numerical>
+    UserReactionClient<'_, TS, TU, TI, TR>
+{
+    /// Sum and count of reactions given by this user to items of type `TI`.
+    ///
+    /// # Errors
+    /// Will return error when internal store failed.
+    pub async fn aggregate(&mut self) -> Result<Aggregate<TR::Item>> {
+        let mut txn = self.store.begin_txn().await?;
+        let result = TR::get_user_aggregate::<TU, TI>(&mut txn, &self.user).await;
+        finish(txn, result).await
+    }
+
+    /// Totals of reactions given by this user, as in `client.given().sum()`.
+    pub fn given(&mut self) -> Totals<'_, Self> {
+        Totals { client: self }
+    }
+}
+
+/// Totals of numerical reactions received by an item, or given by a user, see
+/// [`ItemReactionClient::received`] and [`UserReactionClient::given`].
+#[derive(Debug)]
+pub struct Totals<'a, C> {
+    client: &'a mut C,
+}
+
+impl<TS: Store, TI: ItemType, TR: ReactionType + Numerical>
+    Totals<'_, ItemReactionClient<'_, TS, TI, TR>>
+{
+    /// Sum of values of the reactions.
+    ///
+    /// # Errors
+    /// Will return error when internal store failed.
+    pub async fn sum(&mut self) -> Result<TR::Item> {
+        Ok(self.client.aggregate().await?.sum)
+    }
+
+    /// Number of the reactions.
+    ///
+    /// # Errors
+    /// Will return error when internal store failed.
+    pub async fn count(&mut self) -> Result<usize> {
+        Ok(self.client.aggregate().await?.count)
+    }
+}
+
+impl<TS: Store, TU: UserType, TI: ItemType, TR: ReactionType + Numerical>
+    Totals<'_, UserReactionClient<'_, TS, TU, TI, TR>>
+{
+    /// Sum of values of the reactions.
+    ///
+    /// # Errors
+    /// Will return error when internal store failed.
+    pub async fn sum(&mut self) -> Result<TR::Item> {
+        Ok(self.client.aggregate().await?.sum)
+    }
+
+    /// Number of the reactions.
+    ///
+    /// # Errors
+    /// Will return error when internal store failed.
+    pub async fn count(&mut self) -> Result<usize> {
+        Ok(self.client.aggregate().await?.count)
+    }
+}
+
+/// Commit `txn` if `result` is ok, otherwise roll it back.
+async fn finish<T: Transaction, R>(mut txn: T, result: Result<R>) -> Result<R> {
+    match result {
+        Ok(r) => {
+            txn.commit().await?;
+            Ok(r)
+        }
+        Err(e) => {
+            if let Err(err) = txn.rollback().await {
+                tracing::warn!("failed to rollback transaction: {err}");
+            }
+            Err(e)
+        }
     }
 }
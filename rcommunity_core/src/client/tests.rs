@@ -0,0 +1,260 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::Error,
+    ops::Aggregate,
+    store::{memory::MemoryStore, Direction, Store},
+    Enumerable, ItemType, Numerical, Once, ReactionType, UserType, ID,
+};
+
+use super::{ItemReactionClient, UserItemUnboundedReactionClient, UserReactionClient};
+
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+struct User(usize);
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+struct Item(usize);
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+struct Vote(i64);
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+struct Comment(String);
+
+impl UserType for User {}
+impl ID for User {}
+
+impl ItemType for Item {}
+impl ID for Item {}
+
+impl ReactionType for Vote {}
+impl Once for Vote {}
+impl Enumerable for Vote {}
+impl Numerical for Vote {
+    type Item = i64;
+    fn value(&self) -> i64 {
+        self.0
+    }
+}
+
+impl ReactionType for Comment {}
+
+#[tokio::test]
+async fn test_react_and_unreact() {
+    let mut store = MemoryStore::default();
+
+    let mut client = UserItemUnboundedReactionClient::<_, _, _, Comment>::new(
+        &mut store,
+        User(1000),
+        Item(2000),
+    );
+    let r1 = client.react(Comment("hello".to_string())).await.unwrap();
+    assert_eq!(r1.replaced, None);
+    let r2 = client.react(Comment("world".to_string())).await.unwrap();
+    assert_eq!(
+        client.unreact(&r1.rid).await.unwrap(),
+        Comment("hello".to_string())
+    );
+    assert!(matches!(
+        client.unreact(&r1.rid).await,
+        Err(Error::NotFound { .. })
+    ));
+
+    // reactions of other user-item pairs are not touched
+    let mut other = UserItemUnboundedReactionClient::<_, _, _, Comment>::new(
+        &mut store,
+        User(1001),
+        Item(2000),
+    );
+    let r3 = other.react(Comment("again".to_string())).await.unwrap();
+    assert!(matches!(
+        other.unreact(&r2.rid).await,
+        Err(Error::NotFound { .. })
+    ));
+
+    let mut client = UserItemUnboundedReactionClient::<_, _, _, Comment>::new(
+        &mut store,
+        User(1000),
+        Item(2000),
+    );
+    client.react(Comment("!".to_string())).await.unwrap();
+    assert_eq!(client.unreact_all().await.unwrap().len(), 2);
+    assert!(client.unreact_all().await.unwrap().is_empty());
+
+    let mut other = UserItemUnboundedReactionClient::<_, _, _, Comment>::new(
+        &mut store,
+        User(1001),
+        Item(2000),
+    );
+    assert_eq!(other.unreact_all().await.unwrap(), vec![r3.rid]);
+}
+
+#[tokio::test]
+async fn test_clear() {
+    let mut store = MemoryStore::default();
+
+    let mut client =
+        UserItemUnboundedReactionClient::<_, _, _, Vote>::new(&mut store, User(1000), Item(2000));
+    assert_eq!(client.clear().await.unwrap(), None);
+    assert_eq!(client.get().await.unwrap(), None);
+    client.react(Vote(1)).await.unwrap();
+    let r = client.react(Vote(-1)).await.unwrap();
+    assert_eq!(r.replaced, Some(Vote(1)));
+    assert_eq!(client.get().await.unwrap(), Some(Vote(-1)));
+    assert_eq!(client.clear().await.unwrap(), Some(Vote(-1)));
+    assert_eq!(client.get().await.unwrap(), None);
+    assert_eq!(client.clear().await.unwrap(), None);
+    assert!(matches!(
+        client.unreact(&r.rid).await,
+        Err(Error::NotFound { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_totals() {
+    let mut store = MemoryStore::default();
+
+    for (user, item, vote) in [(1000, 2000, 1), (1001, 2000, 1), (1000, 2001, -1)] {
+        let mut client = UserItemUnboundedReactionClient::<_, _, _, Vote>::new(
+            &mut store,
+            User(user),
+            Item(item),
+        );
+        client.react(Vote(vote)).await.unwrap();
+    }
+
+    let mut client = ItemReactionClient::<_, _, Vote>::new(&mut store, Item(2000));
+    assert_eq!(client.received().sum().await.unwrap(), 2);
+    assert_eq!(client.received().count().await.unwrap(), 2);
+    let mut client = UserReactionClient::<_, _, Item, Vote>::new(&mut store, User(1000));
+    assert_eq!(client.given().sum().await.unwrap(), 0);
+    assert_eq!(client.given().count().await.unwrap(), 2);
+    assert_eq!(
+        client.aggregate().await.unwrap(),
+        Aggregate { sum: 0, count: 2 }
+    );
+}
+
+#[tokio::test]
+async fn test_list_by_value_page() {
+    let mut store = MemoryStore::default();
+    let mut created = Vec::new();
+    for i in 0..5 {
+        let mut client =
+            UserItemUnboundedReactionClient::<_, _, _, Vote>::new(&mut store, User(i), Item(2000));
+        created.push(client.react(Vote(1)).await.unwrap().rid);
+    }
+    let mut client =
+        UserItemUnboundedReactionClient::<_, _, _, Vote>::new(&mut store, User(5), Item(2000));
+    client.react(Vote(-1)).await.unwrap();
+
+    let mut client = ItemReactionClient::<_, _, Vote>::new(&mut store, Item(2000));
+    let all = client.list_by_value(Vote(1)).await.unwrap();
+    // reactions are listed in creation order
+    assert_eq!(all, created);
+    assert_eq!(
+        client
+            .list_by_value_page(Vote(1), None, Direction::Backward, 2)
+            .await
+            .unwrap(),
+        vec![created[4].clone(), created[3].clone()]
+    );
+    for direction in [Direction::Forward, Direction::Backward] {
+        let mut rids = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = client
+                .list_by_value_page(Vote(1), cursor.as_deref(), direction, 2)
+                .await
+                .unwrap();
+            if page.is_empty() {
+                break;
+            }
+            assert!(page.len() <= 2);
+            cursor = page.last().cloned();
+            rids.extend(page);
+        }
+        if direction == Direction::Backward {
+            rids.reverse();
+        }
+        assert_eq!(rids, all);
+    }
+    assert_eq!(
+        client
+            .list_by_value_page(Vote(1), Some(&all[1]), Direction::Backward, 10)
+            .await
+            .unwrap(),
+        vec![all[0].clone()]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_votes() {
+    let store = MemoryStore::default();
+
+    // votes on a hot item conflict with each other, and are retried
+    let tasks: Vec<_> = (0..20)
+        .map(|i| {
+            let mut store = store.clone();
+            tokio::spawn(async move {
+                let mut client = UserItemUnboundedReactionClient::<_, _, _, Vote>::new(
+                    &mut store,
+                    User(i),
+                    Item(2000),
+                );
+                client.react(Vote(1)).await.unwrap();
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    let mut store = store;
+    let mut client = ItemReactionClient::<_, _, Vote>::new(&mut store, Item(2000));
+    assert_eq!(
+        client.aggregate().await.unwrap(),
+        Aggregate { sum: 20, count: 20 }
+    );
+}
+
+/// Check that concurrent [`Once`] reactions of the same user on `store` leave
+/// exactly one reaction, counted once.
+pub(crate) async fn check_concurrent_once<TS: Store + Clone + Sync + 'static>(store: TS) {
+    let tasks: Vec<_> = (1..=10)
+        .map(|i| {
+            let mut store = store.clone();
+            tokio::spawn(async move {
+                let mut client = UserItemUnboundedReactionClient::<_, _, _, Vote>::new(
+                    &mut store,
+                    User(1000),
+                    Item(2000),
+                );
+                client.react(Vote(i)).await.unwrap();
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    let mut store = store;
+    let mut client =
+        UserItemUnboundedReactionClient::<_, _, _, Vote>::new(&mut store, User(1000), Item(2000));
+    let vote = client.get().await.unwrap().unwrap();
+    let mut client = ItemReactionClient::<_, _, Vote>::new(&mut store, Item(2000));
+    assert_eq!(
+        client.aggregate().await.unwrap(),
+        Aggregate {
+            sum: vote.0,
+            count: 1
+        }
+    );
+    let mut listed = 0;
+    for i in 1..=10 {
+        listed += client.list_by_value(Vote(i)).await.unwrap().len();
+    }
+    assert_eq!(listed, 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_once() {
+    check_concurrent_once(MemoryStore::default()).await;
+}
@@ -6,10 +6,90 @@ use thiserror::Error;
 pub enum Error {
     #[error("Not yet implemented.")]
     NotImplemented,
+    #[error(
+        "Reaction not found (type: {reaction_type}, ID: {}).",
+        .rid.as_deref().unwrap_or("-")
+    )]
+    NotFound {
+        reaction_type: &'static str,
+        /// ID of the reaction, if looked up by ID.
+        rid: Option<String>,
+    },
+    #[error("Reaction conflicts with existing one (type: {reaction_type}, ID: {rid}).")]
+    Conflict {
+        reaction_type: &'static str,
+        /// ID of the existing reaction.
+        rid: String,
+    },
+    #[error("Sum of reactions overflowed (type: {reaction_type}).")]
+    Overflow { reaction_type: &'static str },
+    #[error("Transaction aborted by concurrent updates.")]
+    TransactionConflict,
+    #[error("Store error: {0}.")]
+    StoreError(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[error("Corrupt data at key {key:?}: {source}.")]
+    CorruptData {
+        key: Vec<u8>,
+        source: crate::store::format::error::Error,
+    },
+    #[error("Store format version {found} is newer than supported version {supported}.")]
+    UnsupportedVersion { found: u32, supported: u32 },
+    #[error("Store format version {found} is older than current version {current}, and must be migrated.")]
+    MigrationRequired { found: u32, current: u32 },
+    #[error("No migration from store format version {version}.")]
+    MissingMigration { version: u32 },
     #[error("Unknown error: {0}.")]
     UnknownError(String),
     #[error("Serialization error: {0}.")]
     SerializationError(#[from] crate::store::format::error::Error),
 }
 
+impl From<crate::store::format::error::KeyError> for Error {
+    fn from(e: crate::store::format::error::KeyError) -> Self {
+        Error::CorruptData {
+            key: e.key,
+            source: e.source,
+        }
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl From<::tokio_postgres::Error> for Error {
+    fn from(e: ::tokio_postgres::Error) -> Self {
+        use ::tokio_postgres::error::SqlState;
+        match e.code() {
+            Some(&SqlState::T_R_SERIALIZATION_FAILURE | &SqlState::T_R_DEADLOCK_DETECTED) => {
+                Error::TransactionConflict
+            }
+            _ => Error::StoreError(Box::new(e)),
+        }
+    }
+}
+
+#[cfg(feature = "redb")]
+impl From<::redb::Error> for Error {
+    fn from(e: ::redb::Error) -> Self {
+        Error::StoreError(Box::new(e))
+    }
+}
+
+#[cfg(feature = "redis")]
+impl From<::redis::RedisError> for Error {
+    fn from(e: ::redis::RedisError) -> Self {
+        Error::StoreError(Box::new(e))
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<::rusqlite::Error> for Error {
+    fn from(e: ::rusqlite::Error) -> Self {
+        match e.sqlite_error_code() {
+            Some(::rusqlite::ErrorCode::DatabaseBusy | ::rusqlite::ErrorCode::DatabaseLocked) => {
+                Error::TransactionConflict
+            }
+            _ => Error::StoreError(Box::new(e)),
+        }
+    }
+}
+
 pub type Result<T> = core::result::Result<T, Error>;
@@ -1,6 +1,19 @@
 //! This crate contains core facilities for [rcommunity].
 //!
+//! # Upgrading existing stores
+//!
+//! Stores written by earlier releases, before the format version was recorded,
+//! are of format version 1, and **cannot be migrated automatically**. Opening
+//! one fails with [`Error::MigrationRequired`]. Keys of version 1 do not
+//! delimit strings, so they cannot be decoded without knowing the types they
+//! hold, and no built-in migration ships for them. Such a store must be
+//! upgraded by a [`Migration`] written for the user, item and reaction types
+//! of the application, run by a [`Migrator`], or rebuilt from the source data.
+//!
 //! [rcommunity]: ../rcommunity/index.html
+//! [`Error::MigrationRequired`]: error::Error::MigrationRequired
+//! [`Migration`]: store::migration::Migration
+//! [`Migrator`]: store::migration::Migrator
 
 // Global clippy settings.
 #![warn(clippy::pedantic)]
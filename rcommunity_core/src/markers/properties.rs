@@ -1,5 +1,81 @@
 //! Traits to mark reaction properties.
 
+use serde::{de::DeserializeOwned, Serialize};
+
 pub trait ID {}
-pub trait Once {}
 pub trait Enumerable {}
+
+/// Reactions made at most once for each user-item pair.
+pub trait Once {
+    /// How to handle reacting again on the same user-item pair.
+    const MODE: OnceMode = OnceMode::Replace;
+}
+
+/// Behavior of [`Once`] reactions when the user has already reacted on the
+/// item.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum OnceMode {
+    /// Replace the existing reaction with the new one.
+    Replace,
+    /// Reject the new reaction with a conflict error.
+    Reject,
+}
+
+/// Reactions carrying a numerical value, of which sums are maintained for each
+/// item and each user.
+pub trait Numerical {
+    /// Type of the numerical value.
+    type Item: NumericalValue;
+    /// Numerical value of this reaction.
+    fn value(&self) -> Self::Item;
+}
+
+/// Values of [`Numerical`] reactions, summed with overflow checked.
+pub trait NumericalValue:
+    Default + Copy + Serialize + DeserializeOwned + Send + Sync + Sized
+{
+    /// Sum of `self` and `rhs`, or `None` on overflow.
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+    /// Difference of `self` and `rhs`, or `None` on overflow.
+    fn checked_sub(self, rhs: Self) -> Option<Self>;
+}
+
+macro_rules! impl_numerical {
+    ($($t:ty),*) => {
+        $(impl Numerical for $t {
+            type Item = $t;
+            fn value(&self) -> $t {
+                *self
+            }
+        })*
+    };
+}
+
+macro_rules! impl_numerical_value {
+    (int: $($t:ty),*) => {
+        $(impl NumericalValue for $t {
+            fn checked_add(self, rhs: $t) -> Option<$t> {
+                <$t>::checked_add(self, rhs)
+            }
+            fn checked_sub(self, rhs: $t) -> Option<$t> {
+                <$t>::checked_sub(self, rhs)
+            }
+        })*
+    };
+    // floats overflow to infinity
+    (float: $($t:ty),*) => {
+        $(impl NumericalValue for $t {
+            fn checked_add(self, rhs: $t) -> Option<$t> {
+                Some(self + rhs).filter(|v| v.is_finite())
+            }
+            fn checked_sub(self, rhs: $t) -> Option<$t> {
+                Some(self - rhs).filter(|v| v.is_finite())
+            }
+        })*
+    };
+}
+
+// Primitive values can be used as fields of numerical reactions.
+impl_numerical!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64);
+impl_numerical_value!(int: i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+impl_numerical_value!(float: f32, f64);
@@ -2,21 +2,25 @@ use async_trait::async_trait;
 use serde::de::DeserializeOwned;
 
 use crate::{
-    error::Result,
-    markers::{ItemType, Once, ReactionType, UserType},
-    store::Transaction,
+    error::{Error, Result},
+    markers::{ItemType, Once, OnceMode, ReactionType, UserType},
+    store::{format::typename, Transaction},
 };
 
 use super::{reaction_info::ReactionInfoOnce, Reactor};
 
 #[async_trait]
 pub trait BeforeStore {
+    /// Prepare for storing a new reaction. Returns the reaction replaced by the
+    /// new one, if any.
     async fn before_store<TU: UserType + DeserializeOwned, TI: ItemType + DeserializeOwned>(
         &self,
         txn: &mut impl Transaction,
         user: &TU,
         item: &TI,
-    ) -> Result<()>;
+    ) -> Result<Option<Self>>
+    where
+        Self: Sized;
 }
 
 #[async_trait]
@@ -26,16 +30,21 @@ impl<T: ReactionType + DeserializeOwned> BeforeStore for T {
         _txn: &mut impl Transaction,
         _user: &TU,
         _item: &TI,
-    ) -> Result<()>
+    ) -> Result<Option<Self>>
     where
         TU: UserType + DeserializeOwned,
         TI: ItemType + DeserializeOwned,
     {
         // by default do nothing
-        Ok(())
+        Ok(None)
     }
 }
 
+/// Specialized [`BeforeStore`] implementor for reaction types that are marked
+/// as [`Once`].
+///
+/// Existing reaction of the same user-item pair is either replaced or
+/// rejected, according to [`Once::MODE`].
 #[async_trait]
 impl<T: ReactionType + DeserializeOwned + Once> BeforeStore for T {
     async fn before_store<TU, TI>(
@@ -43,15 +52,22 @@ impl<T: ReactionType + DeserializeOwned + Once> BeforeStore for T {
         txn: &mut impl Transaction,
         user: &TU,
         item: &TI,
-    ) -> Result<()>
+    ) -> Result<Option<Self>>
     where
         TU: UserType + DeserializeOwned,
         TI: ItemType + DeserializeOwned,
     {
-        let rid = T::get_rid(txn, user, item).await;
-        if let Ok(r) = &rid {
-            T::dereact::<TU, TI>(txn, r).await?;
+        let rid = match T::get_rid(txn, user, item).await {
+            Ok(rid) => rid,
+            Err(Error::NotFound { .. }) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        match T::MODE {
+            OnceMode::Replace => Ok(Some(T::dereact::<TU, TI>(txn, &rid).await?)),
+            OnceMode::Reject => Err(Error::Conflict {
+                reaction_type: typename::<T>(),
+                rid,
+            }),
         }
-        Ok(())
     }
 }
@@ -1,11 +1,64 @@
 use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::{
     error::Result,
     markers::{Enumerable, ItemType, ReactionType, UserType},
-    store::Transaction,
+    store::{
+        format::{from_keys, tagged, to_key, to_key_range, TypeName},
+        Direction, Transaction,
+    },
 };
 
+#[derive(Serialize)]
+#[serde(rename = "EnumIndexKey")]
+pub struct EnumIndexKeyRef<'a, TI, TR>
+where
+    TI: ItemType,
+    TR: ReactionType,
+{
+    pub reaction_type: TypeName<TR>,
+    #[serde(serialize_with = "tagged::serialize::<TI, _, _>")]
+    pub item: &'a TI,
+    #[serde(serialize_with = "tagged::serialize::<TR, _, _>")]
+    pub reaction: &'a TR,
+    pub rid: &'a str,
+}
+
+// only `rid` is needed when listing, other fields are decoded to skip over
+#[allow(dead_code)]
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "TI: Deserialize<'de>, TR: Deserialize<'de>"))]
+pub(crate) struct EnumIndexKey<TI, TR>
+where
+    TI: ItemType,
+    TR: ReactionType,
+{
+    pub reaction_type: TypeName<TR>,
+    #[serde(deserialize_with = "tagged::deserialize")]
+    pub item: TI,
+    #[serde(deserialize_with = "tagged::deserialize")]
+    pub reaction: TR,
+    pub rid: String,
+}
+
+/// Key prefix of all [`EnumIndexKeyRef`] entries with the same reaction value
+/// on an item.
+#[derive(Serialize)]
+#[serde(rename = "EnumIndexKey")]
+struct EnumIndexPrefixRef<'a, TI, TR>
+where
+    TI: ItemType,
+    TR: ReactionType,
+{
+    reaction_type: TypeName<TR>,
+    #[serde(serialize_with = "tagged::serialize::<TI, _, _>")]
+    item: &'a TI,
+    #[serde(serialize_with = "tagged::serialize::<TR, _, _>")]
+    reaction: &'a TR,
+    rid: (),
+}
+
 #[async_trait]
 pub trait EnumIndex {
     async fn store_enum_index(
@@ -48,41 +101,139 @@ impl<T: ReactionType> EnumIndex for T {
     }
 }
 
+/// Specialized [`EnumIndex`] implementor for reaction types that are marked as
+/// [`Enumerable`].
+///
+/// Under the hood, this implementor manages an index ordered by **item** and
+/// **reaction value**, so that reactions with the same value on an item can be
+/// listed and counted.
 #[async_trait]
 impl<T: ReactionType + Enumerable> EnumIndex for T {
     async fn store_enum_index(
         &self,
-        _txn: &mut impl Transaction,
-        _rid: &str,
+        txn: &mut impl Transaction,
+        rid: &str,
         _user: &impl UserType,
-        _item: &impl ItemType,
+        item: &impl ItemType,
     ) -> Result<()> {
-        //     let typename = typename::<T>();
-        //     let key = format!(
-        //         "{typename}_{}_{}_{}_{rid}",
-        //         Serializable::serialize(user),
-        //         Serializable::serialize(item),
-        //         Serializable::serialize(self)
-        //     );
-        //     txn.put(key.as_bytes(), b"").await?;
-        //     Ok(())
-        // }
-        // async fn discard_enum_index(
-        //     &self,
-        //     txn: &mut impl Transaction,
-        //     rid: &str,
-        //     user: &impl UserType,
-        //     item: &impl ItemType,
-        // ) -> Result<()> {
-        //     let typename = typename::<T>();
-        //     let key = format!(
-        //         "{typename}_{}_{}_{}_{rid}",
-        //         Serializable::serialize(user),
-        //         Serializable::serialize(item),
-        //         Serializable::serialize(self)
-        //     );
-        //     txn.delete(key.as_bytes()).await?;
-        //     Ok(())
-        todo!()
+        let key = EnumIndexKeyRef {
+            reaction_type: TypeName::<T>::new(),
+            item,
+            reaction: self,
+            rid,
+        };
+        txn.put(&to_key(&key)?, b"").await?;
+        Ok(())
+    }
+    async fn discard_enum_index(
+        &self,
+        txn: &mut impl Transaction,
+        rid: &str,
+        _user: &impl UserType,
+        item: &impl ItemType,
+    ) -> Result<()> {
+        let key = EnumIndexKeyRef {
+            reaction_type: TypeName::<T>::new(),
+            item,
+            reaction: self,
+            rid,
+        };
+        txn.delete(&to_key(&key)?).await?;
+        Ok(())
+    }
+}
+
+/// Ability to query reactions of [`Enumerable`] types by reaction value.
+#[async_trait]
+pub trait EnumIndexLookup: ReactionType + DeserializeOwned {
+    /// List IDs of all reactions on `item` with the same value as `self`.
+    async fn list_by_value<TI>(&self, txn: &mut impl Transaction, item: &TI) -> Result<Vec<String>>
+    where
+        TI: ItemType + DeserializeOwned;
+    /// List a page of at most `limit` IDs of reactions on `item` with the same
+    /// value as `self`, continuing from reaction `cursor` exclusively in
+    /// `direction`.
+    async fn list_by_value_page<TI>(
+        &self,
+        txn: &mut impl Transaction,
+        item: &TI,
+        cursor: Option<&str>,
+        direction: Direction,
+        limit: usize,
+    ) -> Result<Vec<String>>
+    where
+        TI: ItemType + DeserializeOwned;
+    /// Count reactions on `item` with the same value as `self`.
+    async fn count_by_value<TI>(&self, txn: &mut impl Transaction, item: &TI) -> Result<usize>
+    where
+        TI: ItemType + DeserializeOwned;
+}
+
+#[async_trait]
+impl<T: ReactionType + DeserializeOwned + Enumerable> EnumIndexLookup for T {
+    async fn list_by_value<TI>(&self, txn: &mut impl Transaction, item: &TI) -> Result<Vec<String>>
+    where
+        TI: ItemType + DeserializeOwned,
+    {
+        let (start, end) = to_key_range(&EnumIndexPrefixRef {
+            reaction_type: TypeName::<T>::new(),
+            item,
+            reaction: self,
+            rid: (),
+        })?;
+        let mut rids = Vec::new();
+        for key in from_keys(txn.scan_keys(&start, &end, usize::MAX).await?) {
+            let key: EnumIndexKey<TI, T> = key?;
+            rids.push(key.rid);
+        }
+        Ok(rids)
+    }
+    async fn list_by_value_page<TI>(
+        &self,
+        txn: &mut impl Transaction,
+        item: &TI,
+        cursor: Option<&str>,
+        direction: Direction,
+        limit: usize,
+    ) -> Result<Vec<String>>
+    where
+        TI: ItemType + DeserializeOwned,
+    {
+        let (start, end) = to_key_range(&EnumIndexPrefixRef {
+            reaction_type: TypeName::<T>::new(),
+            item,
+            reaction: self,
+            rid: (),
+        })?;
+        let cursor = match cursor {
+            Some(rid) => Some(to_key(&EnumIndexKeyRef {
+                reaction_type: TypeName::<T>::new(),
+                item,
+                reaction: self,
+                rid,
+            })?),
+            None => None,
+        };
+        let keys = txn
+            .scan_keys_page(&start, &end, cursor.as_deref(), direction, limit)
+            .await?;
+        let mut rids = Vec::new();
+        for key in from_keys(keys) {
+            let key: EnumIndexKey<TI, T> = key?;
+            rids.push(key.rid);
+        }
+        Ok(rids)
+    }
+    async fn count_by_value<TI>(&self, txn: &mut impl Transaction, item: &TI) -> Result<usize>
+    where
+        TI: ItemType + DeserializeOwned,
+    {
+        let (start, end) = to_key_range(&EnumIndexPrefixRef {
+            reaction_type: TypeName::<T>::new(),
+            item,
+            reaction: self,
+            rid: (),
+        })?;
+        Ok(txn.scan_keys(&start, &end, usize::MAX).await?.count())
     }
 }
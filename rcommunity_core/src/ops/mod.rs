@@ -1,38 +1,46 @@
 //! Traits for supporting all the internal operations.
 
 use async_trait::async_trait;
-use serde::de::DeserializeOwned;
+use serde::{de::DeserializeOwned, Deserialize};
 
 use crate::{
-    error::Result,
+    error::{Error, Result},
     markers::{ItemType, ReactionType, UserType},
-    store::Transaction,
+    store::{format::from_value, Transaction},
 };
 
 mod before_store;
 mod reaction_info;
 mod unique_index;
 mod enum_index;
+mod numerical_aggregate;
 
 #[cfg(test)]
-mod tests;
+pub(crate) mod tests;
 
 pub use before_store::BeforeStore;
-pub use enum_index::EnumIndex;
+pub use enum_index::{EnumIndex, EnumIndexLookup};
+pub use numerical_aggregate::{Aggregate, NumericalAggregate, NumericalAggregateLookup};
 pub use reaction_info::{ReactionInfo, ReactionInfoOnce};
-pub use unique_index::UniqueIndex;
+pub use unique_index::{UniqueIndex, UniqueIndexLookup};
 
 #[async_trait]
 pub trait Reactor {
+    /// Store a new reaction. Returns the reaction replaced by the new one, if
+    /// any.
     async fn react(
         &self,
         txn: &mut impl Transaction,
         rid: &str,
         user: &(impl UserType + DeserializeOwned),
         item: &(impl ItemType + DeserializeOwned),
-    ) -> Result<()>;
-    async fn dereact<TU, TI>(txn: &mut impl Transaction, rid: &str) -> Result<()>
+    ) -> Result<Option<Self>>
     where
+        Self: Sized;
+    /// Remove a reaction by ID. Returns the removed reaction.
+    async fn dereact<TU, TI>(txn: &mut impl Transaction, rid: &str) -> Result<Self>
+    where
+        Self: Sized,
         TU: UserType + DeserializeOwned,
         TI: ItemType + DeserializeOwned;
 }
@@ -45,14 +53,16 @@ impl<T: ReactionType + DeserializeOwned> Reactor for T {
         rid: &str,
         user: &(impl UserType + DeserializeOwned),
         item: &(impl ItemType + DeserializeOwned),
-    ) -> Result<()> {
-        self.before_store(txn, user, item).await?;
-        self.store_reaction(txn, rid, user, item).await?;
+    ) -> Result<Option<Self>> {
+        let replaced = self.before_store(txn, user, item).await?;
+        // check uniqueness before anything is written
         self.store_unique_index(txn, rid, user, item).await?;
+        self.store_reaction(txn, rid, user, item).await?;
         self.store_enum_index(txn, rid, user, item).await?;
-        Ok(())
+        self.store_aggregate(txn, user, item).await?;
+        Ok(replaced)
     }
-    async fn dereact<TU, TI>(txn: &mut impl Transaction, rid: &str) -> Result<()>
+    async fn dereact<TU, TI>(txn: &mut impl Transaction, rid: &str) -> Result<Self>
     where
         TU: UserType + DeserializeOwned,
         TI: ItemType + DeserializeOwned,
@@ -61,9 +71,18 @@ impl<T: ReactionType + DeserializeOwned> Reactor for T {
         let user = r.user;
         let item = r.item;
         let r = r.reaction;
+        r.discard_aggregate(txn, &user, &item).await?;
         r.discard_enum_index(txn, rid, &user, &item).await?;
         r.discard_unique_index(txn, rid, &user, &item).await?;
         r.discard_reaction(txn, rid, &user, &item).await?;
-        Ok(())
+        Ok(r)
     }
 }
+
+/// Deserialize a value read from store at `key`.
+fn decode_value<'a, T: Deserialize<'a>>(key: &[u8], value: &'a [u8]) -> Result<T> {
+    from_value(value).map_err(|source| Error::CorruptData {
+        key: key.to_vec(),
+        source,
+    })
+}
@@ -0,0 +1,236 @@
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    markers::{ItemType, Numerical, NumericalValue, ReactionType, UserType},
+    store::{
+        format::{tagged, to_key, typename, TypeName},
+        Transaction,
+    },
+};
+
+use super::decode_value;
+
+#[derive(Serialize)]
+#[serde(rename = "ItemAggregateKey")]
+pub struct ItemAggregateKeyRef<'a, TI, TR>
+where
+    TI: ItemType,
+    TR: ReactionType,
+{
+    pub reaction_type: TypeName<TR>,
+    #[serde(serialize_with = "tagged::serialize::<TI, _, _>")]
+    pub item: &'a TI,
+}
+
+/// Key of sums given by a user, on items of one type.
+#[derive(Serialize)]
+#[serde(rename = "UserAggregateKey")]
+pub struct UserAggregateKeyRef<'a, TU, TI, TR>
+where
+    TU: UserType,
+    TI: ItemType,
+    TR: ReactionType,
+{
+    pub reaction_type: TypeName<TR>,
+    pub item_type: TypeName<TI>,
+    #[serde(serialize_with = "tagged::serialize::<TU, _, _>")]
+    pub user: &'a TU,
+}
+
+/// Sum and count of [`Numerical`] reactions.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, Eq, PartialEq)]
+#[serde(rename = "AggregateValue")]
+pub struct Aggregate<T> {
+    pub sum: T,
+    pub count: usize,
+}
+
+#[async_trait]
+pub trait NumericalAggregate {
+    async fn store_aggregate<TU: UserType, TI: ItemType>(
+        &self,
+        txn: &mut impl Transaction,
+        user: &TU,
+        item: &TI,
+    ) -> Result<()>;
+    async fn discard_aggregate<TU: UserType, TI: ItemType>(
+        &self,
+        txn: &mut impl Transaction,
+        user: &TU,
+        item: &TI,
+    ) -> Result<()>;
+}
+
+#[async_trait]
+impl<T: ReactionType> NumericalAggregate for T {
+    default async fn store_aggregate<TU: UserType, TI: ItemType>(
+        &self,
+        _txn: &mut impl Transaction,
+        _user: &TU,
+        _item: &TI,
+    ) -> Result<()> {
+        // by default do nothing
+        Ok(())
+    }
+    default async fn discard_aggregate<TU: UserType, TI: ItemType>(
+        &self,
+        _txn: &mut impl Transaction,
+        _user: &TU,
+        _item: &TI,
+    ) -> Result<()> {
+        // by default do nothing
+        Ok(())
+    }
+}
+
+/// Specialized [`NumericalAggregate`] implementor for reaction types that are
+/// marked as [`Numerical`].
+///
+/// Under the hood, this implementor maintains sum and count of reaction values
+/// received by each item, and given by each user on each item type. Sums
+/// overflowing [`Numerical::Item`] fail with [`Error::Overflow`].
+#[async_trait]
+impl<T: ReactionType + Numerical> NumericalAggregate for T {
+    async fn store_aggregate<TU: UserType, TI: ItemType>(
+        &self,
+        txn: &mut impl Transaction,
+        user: &TU,
+        item: &TI,
+    ) -> Result<()> {
+        let value = self.value();
+        let add = |a: Aggregate<T::Item>| {
+            Some(Aggregate {
+                sum: a.sum.checked_add(value)?,
+                count: a.count + 1,
+            })
+        };
+        let key = ItemAggregateKeyRef {
+            reaction_type: TypeName::<T>::new(),
+            item,
+        };
+        update_aggregate::<T>(txn, &to_key(&key)?, add).await?;
+        let key = UserAggregateKeyRef {
+            reaction_type: TypeName::<T>::new(),
+            item_type: TypeName::<TI>::new(),
+            user,
+        };
+        update_aggregate::<T>(txn, &to_key(&key)?, add).await?;
+        Ok(())
+    }
+    async fn discard_aggregate<TU: UserType, TI: ItemType>(
+        &self,
+        txn: &mut impl Transaction,
+        user: &TU,
+        item: &TI,
+    ) -> Result<()> {
+        let value = self.value();
+        let sub = |a: Aggregate<T::Item>| {
+            Some(Aggregate {
+                sum: a.sum.checked_sub(value)?,
+                count: a.count.saturating_sub(1),
+            })
+        };
+        let key = ItemAggregateKeyRef {
+            reaction_type: TypeName::<T>::new(),
+            item,
+        };
+        update_aggregate::<T>(txn, &to_key(&key)?, sub).await?;
+        let key = UserAggregateKeyRef {
+            reaction_type: TypeName::<T>::new(),
+            item_type: TypeName::<TI>::new(),
+            user,
+        };
+        update_aggregate::<T>(txn, &to_key(&key)?, sub).await?;
+        Ok(())
+    }
+}
+
+/// Ability to query maintained sums of [`Numerical`] reactions.
+#[async_trait]
+pub trait NumericalAggregateLookup: ReactionType + Numerical {
+    /// Sum and count of reactions received by `item`.
+    async fn get_item_aggregate<TI>(
+        txn: &mut impl Transaction,
+        item: &TI,
+    ) -> Result<Aggregate<Self::Item>>
+    where
+        TI: ItemType;
+    /// Sum and count of reactions given by `user` on items of type `TI`.
+    async fn get_user_aggregate<TU, TI>(
+        txn: &mut impl Transaction,
+        user: &TU,
+    ) -> Result<Aggregate<Self::Item>>
+    where
+        TU: UserType,
+        TI: ItemType;
+}
+
+#[async_trait]
+impl<T: ReactionType + Numerical> NumericalAggregateLookup for T {
+    async fn get_item_aggregate<TI>(
+        txn: &mut impl Transaction,
+        item: &TI,
+    ) -> Result<Aggregate<T::Item>>
+    where
+        TI: ItemType,
+    {
+        let key = ItemAggregateKeyRef {
+            reaction_type: TypeName::<T>::new(),
+            item,
+        };
+        get_aggregate(txn, &to_key(&key)?).await
+    }
+    async fn get_user_aggregate<TU, TI>(
+        txn: &mut impl Transaction,
+        user: &TU,
+    ) -> Result<Aggregate<T::Item>>
+    where
+        TU: UserType,
+        TI: ItemType,
+    {
+        let key = UserAggregateKeyRef {
+            reaction_type: TypeName::<T>::new(),
+            item_type: TypeName::<TI>::new(),
+            user,
+        };
+        get_aggregate(txn, &to_key(&key)?).await
+    }
+}
+
+async fn get_aggregate<V>(txn: &mut impl Transaction, key: &[u8]) -> Result<Aggregate<V>>
+where
+    V: Default + DeserializeOwned,
+{
+    match txn.get(key).await? {
+        Some(v) => decode_value(key, &v),
+        None => Ok(Aggregate::default()),
+    }
+}
+
+/// Read-modify-write an aggregate of reactions of type `T` while holding it for
+/// update, failing if `f` overflows. Aggregates that no longer count any
+/// reaction are removed.
+async fn update_aggregate<T>(
+    txn: &mut impl Transaction,
+    key: &[u8],
+    f: impl FnOnce(Aggregate<T::Item>) -> Option<Aggregate<T::Item>> + Send,
+) -> Result<()>
+where
+    T: ReactionType + Numerical,
+{
+    let current = match txn.get_for_update(key).await? {
+        Some(v) => decode_value(key, &v)?,
+        None => Aggregate::default(),
+    };
+    let updated = f(current).ok_or(Error::Overflow {
+        reaction_type: typename::<T>(),
+    })?;
+    if updated.count == 0 {
+        txn.delete(key).await?;
+    } else {
+        txn.put(key, &txn.codec().to_value(&updated)?).await?;
+    }
+    Ok(())
+}
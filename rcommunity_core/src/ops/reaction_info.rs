@@ -5,11 +5,13 @@ use crate::{
     error::{Error, Result},
     markers::{ItemType, Once, ReactionType, UserType},
     store::{
-        format::{from_value, to_key, to_value, TypeName},
+        format::{from_keys, tagged, to_key, to_key_range, typename, TypeName},
         Transaction,
     },
 };
 
+use super::decode_value;
+
 #[derive(Serialize)]
 #[serde(rename = "ReactionInfoKey")]
 pub struct ReactionInfoKeyRef<'a, TR>
@@ -54,11 +56,48 @@ where
     TR: ReactionType,
 {
     pub reaction_type: TypeName<TR>,
+    #[serde(serialize_with = "tagged::serialize::<TU, _, _>")]
     pub user: &'a TU,
+    #[serde(serialize_with = "tagged::serialize::<TI, _, _>")]
     pub item: &'a TI,
     pub rid: &'a str,
 }
 
+// only `rid` is needed when listing, other fields are decoded to skip over
+#[allow(dead_code)]
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "TU: Deserialize<'de>, TI: Deserialize<'de>"))]
+pub(crate) struct UserItemToReactionKey<TU, TI, TR>
+where
+    TU: UserType,
+    TI: ItemType,
+    TR: ReactionType,
+{
+    pub reaction_type: TypeName<TR>,
+    #[serde(deserialize_with = "tagged::deserialize")]
+    pub user: TU,
+    #[serde(deserialize_with = "tagged::deserialize")]
+    pub item: TI,
+    pub rid: String,
+}
+
+/// Key prefix of all [`UserItemToReactionKeyRef`] entries of a user-item pair.
+#[derive(Serialize)]
+#[serde(rename = "UserItemToReactionKey")]
+struct UserItemToReactionPrefixRef<'a, TU, TI, TR>
+where
+    TU: UserType,
+    TI: ItemType,
+    TR: ReactionType,
+{
+    reaction_type: TypeName<TR>,
+    #[serde(serialize_with = "tagged::serialize::<TU, _, _>")]
+    user: &'a TU,
+    #[serde(serialize_with = "tagged::serialize::<TI, _, _>")]
+    item: &'a TI,
+    rid: (),
+}
+
 #[derive(Serialize)]
 #[serde(rename = "UserItemToReactionValue")]
 pub(crate) struct UserItemToReactionValueRef<'a, TR>
@@ -77,7 +116,9 @@ where
     TR: ReactionType,
 {
     pub reaction_type: TypeName<TR>,
+    #[serde(serialize_with = "tagged::serialize::<TU, _, _>")]
     pub user: &'a TU,
+    #[serde(serialize_with = "tagged::serialize::<TI, _, _>")]
     pub item: &'a TI,
 }
 
@@ -116,6 +157,15 @@ pub trait ReactionInfo: ReactionType {
     where
         TU: UserType + DeserializeOwned,
         TI: ItemType + DeserializeOwned;
+    /// List IDs of all reactions of a user-item pair.
+    async fn get_rids<TU, TI>(
+        txn: &mut impl Transaction,
+        user: &TU,
+        item: &TI,
+    ) -> Result<Vec<String>>
+    where
+        TU: UserType + DeserializeOwned,
+        TI: ItemType + DeserializeOwned;
 }
 
 /// Default [`ReactionInfo`] implementor for all reaction types.
@@ -140,7 +190,8 @@ impl<T: ReactionType + DeserializeOwned> ReactionInfo for T {
             item,
             reaction: self,
         };
-        txn.put(&to_key(&key)?, &to_value(&value)?).await?;
+        txn.put(&to_key(&key)?, &txn.codec().to_value(&value)?)
+            .await?;
         let key = UserItemToReactionKeyRef {
             reaction_type: TypeName::<T>::new(),
             user,
@@ -148,7 +199,8 @@ impl<T: ReactionType + DeserializeOwned> ReactionInfo for T {
             rid,
         };
         let value = UserItemToReactionValueRef { reaction: self };
-        txn.put(&to_key(&key)?, &to_value(&value)?).await?;
+        txn.put(&to_key(&key)?, &txn.codec().to_value(&value)?)
+            .await?;
         Ok(())
     }
     default async fn discard_reaction(
@@ -185,11 +237,37 @@ impl<T: ReactionType + DeserializeOwned> ReactionInfo for T {
             reaction_type: TypeName::<T>::new(),
             rid,
         };
-        let value = txn.get(&to_key(&key)?).await?;
+        let key = to_key(&key)?;
+        let value = txn.get(&key).await?;
         if let Some(v) = value {
-            return Ok(from_value(&v)?);
+            return decode_value(&key, &v);
         }
-        Err(Error::UnknownError("TODO: change to not found".into()))
+        Err(Error::NotFound {
+            reaction_type: typename::<T>(),
+            rid: Some(rid.to_string()),
+        })
+    }
+    default async fn get_rids<TU, TI>(
+        txn: &mut impl Transaction,
+        user: &TU,
+        item: &TI,
+    ) -> Result<Vec<String>>
+    where
+        TU: UserType + DeserializeOwned,
+        TI: ItemType + DeserializeOwned,
+    {
+        let (start, end) = to_key_range(&UserItemToReactionPrefixRef {
+            reaction_type: TypeName::<T>::new(),
+            user,
+            item,
+            rid: (),
+        })?;
+        let mut rids = Vec::new();
+        for key in from_keys(txn.scan_keys(&start, &end, usize::MAX).await?) {
+            let key: UserItemToReactionKey<TU, TI, T> = key?;
+            rids.push(key.rid);
+        }
+        Ok(rids)
     }
 }
 
@@ -217,14 +295,16 @@ impl<T: ReactionType + DeserializeOwned + Once> ReactionInfo for T {
             item,
             reaction: self,
         };
-        txn.put(&to_key(&key)?, &to_value(&value)?).await?;
+        txn.put(&to_key(&key)?, &txn.codec().to_value(&value)?)
+            .await?;
         let key = UserItemToReactionOnceKeyRef {
             reaction_type: TypeName::<T>::new(),
             user,
             item,
         };
         let value = UserItemToReactionOnceValueRef { rid };
-        txn.put(&to_key(&key)?, &to_value(&value)?).await?;
+        txn.put(&to_key(&key)?, &txn.codec().to_value(&value)?)
+            .await?;
         Ok(())
     }
     async fn discard_reaction(
@@ -248,6 +328,21 @@ impl<T: ReactionType + DeserializeOwned + Once> ReactionInfo for T {
         txn.delete(&to_key(&key)?).await?;
         Ok(())
     }
+    async fn get_rids<TU, TI>(
+        txn: &mut impl Transaction,
+        user: &TU,
+        item: &TI,
+    ) -> Result<Vec<String>>
+    where
+        TU: UserType + DeserializeOwned,
+        TI: ItemType + DeserializeOwned,
+    {
+        match T::get_rid(txn, user, item).await {
+            Ok(rid) => Ok(vec![rid]),
+            Err(Error::NotFound { .. }) => Ok(vec![]),
+            Err(e) => Err(e),
+        }
+    }
 }
 
 #[async_trait]
@@ -270,12 +365,18 @@ impl<T: ReactionType + Once> ReactionInfoOnce for T {
             user,
             item,
         };
-        let value = txn.get(&to_key(&key)?).await?;
+        let key = to_key(&key)?;
+        // lock the user-item pair, so that concurrent reactions of the same
+        // user do not both find it free
+        let value = txn.get_for_update(&key).await?;
         if let Some(v) = value {
-            let v: UserItemToReactionOnceValue = from_value(&v)?;
+            let v: UserItemToReactionOnceValue = decode_value(&key, &v)?;
             Ok(v.rid)
         } else {
-            Err(Error::UnknownError("TODO: change to not found".into()))
+            Err(Error::NotFound {
+                reaction_type: typename::<T>(),
+                rid: None,
+            })
         }
     }
 }
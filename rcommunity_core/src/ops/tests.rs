@@ -1,21 +1,44 @@
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    error::Error,
     ops::Reactor,
-    store::{memory::MemoryStore, Store},
-    ItemType, Once, ReactionType, UserType, ID,
+    store::{
+        format::{to_key, Codec, TypeName},
+        memory::MemoryStore,
+        Store, Transaction,
+    },
+    Enumerable, ItemType, Numerical, Once, OnceMode, ReactionType, UserType, ID,
 };
 
-use super::{ReactionInfo, ReactionInfoOnce};
+use super::{
+    numerical_aggregate::ItemAggregateKeyRef, reaction_info::UserItemToReactionOnceKeyRef,
+    Aggregate, EnumIndexLookup, NumericalAggregateLookup, ReactionInfo, ReactionInfoOnce,
+    UniqueIndexLookup,
+};
 
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
 struct User(usize);
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
 struct Item(usize);
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
+struct Thread(usize);
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
 struct Vote(i64);
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
 struct Comment(String);
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
+struct Tag(String);
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+struct Rate(u8);
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
+struct Star(u8);
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
+enum Emoji {
+    Like,
+    Laugh,
+    Custom(String),
+}
 
 impl UserType for User {}
 impl ID for User {}
@@ -23,14 +46,46 @@ impl ID for User {}
 impl ItemType for Item {}
 impl ID for Item {}
 
+impl ItemType for Thread {}
+impl ID for Thread {}
+
 impl ReactionType for Vote {}
 impl Once for Vote {}
+impl Enumerable for Vote {}
+impl Numerical for Vote {
+    type Item = i64;
+    fn value(&self) -> i64 {
+        self.0
+    }
+}
 
 impl ReactionType for Comment {}
 
+impl ReactionType for Tag {}
+impl ID for Tag {}
+
+impl ReactionType for Emoji {}
+impl Enumerable for Emoji {}
+
+impl ReactionType for Rate {}
+impl Once for Rate {
+    const MODE: OnceMode = OnceMode::Reject;
+}
+
+impl ReactionType for Star {}
+impl Numerical for Star {
+    type Item = u8;
+    fn value(&self) -> u8 {
+        self.0
+    }
+}
+
 #[tokio::test]
 async fn test_reaction_info() {
-    let mut store = MemoryStore::default();
+    check_reaction_info(MemoryStore::default()).await;
+}
+
+async fn check_reaction_info(mut store: impl Store) {
     let mut txn = store.begin_txn().await.unwrap();
 
     let user = User(1000);
@@ -58,12 +113,534 @@ async fn test_reaction_info() {
         .unwrap();
     assert!(r.reaction == Vote(1));
     let rid = Vote::get_rid(&mut txn, &user, &item).await.unwrap();
-    assert!(&rid == "r3");
+    assert_eq!(&rid, "r3");
+
+    let replaced = Vote(-1).react(&mut txn, "r4", &user, &item).await.unwrap();
+    assert!(replaced == Some(Vote(1)));
+    assert!(matches!(
+        Vote::get_reaction_by_id::<User, Item>(&mut txn, "r3").await,
+        Err(Error::NotFound { rid: Some(rid), .. }) if rid == "r3"
+    ));
+    let rid = Vote::get_rid(&mut txn, &user, &item).await.unwrap();
+    assert_eq!(&rid, "r4");
+}
+
+#[tokio::test]
+async fn test_unique_index() {
+    check_unique_index(MemoryStore::default()).await;
+}
+
+async fn check_unique_index(mut store: impl Store) {
+    let mut txn = store.begin_txn().await.unwrap();
+
+    let user = User(1000);
+    let item = Item(2000);
+    let rust = Tag("rust".to_string());
+
+    rust.react(&mut txn, "r1", &user, &item).await.unwrap();
+    Tag("go".to_string())
+        .react(&mut txn, "r2", &user, &item)
+        .await
+        .unwrap();
+    assert_eq!(
+        rust.get_unique_rid(&mut txn, &user, &item).await.unwrap(),
+        "r1"
+    );
+
+    // same tag can only be attached once for each user-item pair
+    assert!(matches!(
+        rust.react(&mut txn, "r3", &user, &item).await,
+        Err(Error::Conflict { rid, .. }) if rid == "r1"
+    ));
+    assert!(matches!(
+        Tag::get_reaction_by_id::<User, Item>(&mut txn, "r3").await,
+        Err(Error::NotFound { .. })
+    ));
+    rust.react(&mut txn, "r4", &User(1001), &item)
+        .await
+        .unwrap();
+
+    Tag::dereact::<User, Item>(&mut txn, "r1").await.unwrap();
+    assert!(matches!(
+        rust.get_unique_rid(&mut txn, &user, &item).await,
+        Err(Error::NotFound { rid: None, .. })
+    ));
+    rust.react(&mut txn, "r5", &user, &item).await.unwrap();
+    assert_eq!(
+        rust.get_unique_rid(&mut txn, &user, &item).await.unwrap(),
+        "r5"
+    );
+}
+
+#[tokio::test]
+async fn test_enum_index() {
+    check_enum_index(MemoryStore::default()).await;
+}
+
+async fn check_enum_index(mut store: impl Store) {
+    let mut txn = store.begin_txn().await.unwrap();
+
+    let item = Item(2000);
+
+    Vote(1)
+        .react(&mut txn, "r1", &User(1000), &item)
+        .await
+        .unwrap();
+    Vote(1)
+        .react(&mut txn, "r2", &User(1001), &item)
+        .await
+        .unwrap();
+    Vote(-1)
+        .react(&mut txn, "r3", &User(1002), &item)
+        .await
+        .unwrap();
+    Vote(1)
+        .react(&mut txn, "r4", &User(1000), &Item(2001))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        Vote(1).list_by_value(&mut txn, &item).await.unwrap(),
+        vec!["r1".to_string(), "r2".to_string()]
+    );
+    assert_eq!(Vote(1).count_by_value(&mut txn, &item).await.unwrap(), 2);
+    assert_eq!(Vote(-1).count_by_value(&mut txn, &item).await.unwrap(), 1);
+    assert_eq!(Vote(0).count_by_value(&mut txn, &item).await.unwrap(), 0);
+
+    // replacing a once reaction moves it to the new value
+    Vote(-1)
+        .react(&mut txn, "r5", &User(1001), &item)
+        .await
+        .unwrap();
+    assert_eq!(
+        Vote(1).list_by_value(&mut txn, &item).await.unwrap(),
+        vec!["r1".to_string()]
+    );
+    assert_eq!(
+        Vote(-1).list_by_value(&mut txn, &item).await.unwrap(),
+        vec!["r3".to_string(), "r5".to_string()]
+    );
+
+    Vote::dereact::<User, Item>(&mut txn, "r3").await.unwrap();
+    assert_eq!(Vote(-1).count_by_value(&mut txn, &item).await.unwrap(), 1);
+
+    // enum values are indexed by variant
+    Emoji::Like
+        .react(&mut txn, "r6", &User(1000), &item)
+        .await
+        .unwrap();
+    Emoji::Laugh
+        .react(&mut txn, "r7", &User(1000), &item)
+        .await
+        .unwrap();
+    Emoji::Custom("tada".to_string())
+        .react(&mut txn, "r8", &User(1001), &item)
+        .await
+        .unwrap();
+    assert_eq!(
+        Emoji::Like.list_by_value(&mut txn, &item).await.unwrap(),
+        vec!["r6".to_string()]
+    );
+    assert_eq!(
+        Emoji::Custom("tada".to_string())
+            .list_by_value(&mut txn, &item)
+            .await
+            .unwrap(),
+        vec!["r8".to_string()]
+    );
+    assert_eq!(
+        Emoji::Custom("ta".to_string())
+            .count_by_value(&mut txn, &item)
+            .await
+            .unwrap(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_numerical_aggregate() {
+    check_numerical_aggregate(MemoryStore::default()).await;
+}
+
+async fn check_numerical_aggregate(mut store: impl Store) {
+    let mut txn = store.begin_txn().await.unwrap();
+
+    let item = Item(2000);
+
+    Vote(1)
+        .react(&mut txn, "r1", &User(1000), &item)
+        .await
+        .unwrap();
+    Vote(1)
+        .react(&mut txn, "r2", &User(1001), &item)
+        .await
+        .unwrap();
+    Vote(-1)
+        .react(&mut txn, "r3", &User(1002), &item)
+        .await
+        .unwrap();
+    Vote(1)
+        .react(&mut txn, "r4", &User(1000), &Item(2001))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        Vote::get_item_aggregate(&mut txn, &item).await.unwrap(),
+        Aggregate { sum: 1, count: 3 }
+    );
+    assert_eq!(
+        Vote::get_user_aggregate::<_, Item>(&mut txn, &User(1000))
+            .await
+            .unwrap(),
+        Aggregate { sum: 2, count: 2 }
+    );
+
+    // replacing a once reaction reverts the old value
+    Vote(-1)
+        .react(&mut txn, "r5", &User(1001), &item)
+        .await
+        .unwrap();
+    assert_eq!(
+        Vote::get_item_aggregate(&mut txn, &item).await.unwrap(),
+        Aggregate { sum: -1, count: 3 }
+    );
+    assert_eq!(
+        Vote::get_user_aggregate::<_, Item>(&mut txn, &User(1001))
+            .await
+            .unwrap(),
+        Aggregate { sum: -1, count: 1 }
+    );
+
+    Vote::dereact::<User, Item>(&mut txn, "r4").await.unwrap();
+    assert_eq!(
+        Vote::get_item_aggregate(&mut txn, &Item(2001))
+            .await
+            .unwrap(),
+        Aggregate::default()
+    );
+    assert_eq!(
+        Vote::get_user_aggregate::<_, Item>(&mut txn, &User(1000))
+            .await
+            .unwrap(),
+        Aggregate { sum: 1, count: 1 }
+    );
+}
+
+#[tokio::test]
+async fn test_numerical_aggregate_by_item_type() {
+    check_numerical_aggregate_by_item_type(MemoryStore::default()).await;
+}
+
+async fn check_numerical_aggregate_by_item_type(mut store: impl Store) {
+    let mut txn = store.begin_txn().await.unwrap();
+
+    let user = User(1000);
+    Vote(1)
+        .react(&mut txn, "r1", &user, &Item(2000))
+        .await
+        .unwrap();
+    Vote(-3)
+        .react(&mut txn, "r2", &user, &Thread(2000))
+        .await
+        .unwrap();
+
+    // sums given by a user are kept for each item type
+    assert_eq!(
+        Vote::get_user_aggregate::<_, Item>(&mut txn, &user)
+            .await
+            .unwrap(),
+        Aggregate { sum: 1, count: 1 }
+    );
+    assert_eq!(
+        Vote::get_user_aggregate::<_, Thread>(&mut txn, &user)
+            .await
+            .unwrap(),
+        Aggregate { sum: -3, count: 1 }
+    );
+    Vote::dereact::<User, Thread>(&mut txn, "r2").await.unwrap();
+    assert_eq!(
+        Vote::get_user_aggregate::<_, Thread>(&mut txn, &user)
+            .await
+            .unwrap(),
+        Aggregate::default()
+    );
+    assert_eq!(
+        Vote::get_user_aggregate::<_, Item>(&mut txn, &user)
+            .await
+            .unwrap(),
+        Aggregate { sum: 1, count: 1 }
+    );
+}
+
+#[tokio::test]
+async fn test_numerical_aggregate_overflow() {
+    check_numerical_aggregate_overflow(MemoryStore::default()).await;
+}
+
+async fn check_numerical_aggregate_overflow(mut store: impl Store) {
+    let mut txn = store.begin_txn().await.unwrap();
 
-    Vote(-1).react(&mut txn, "r4", &user, &item).await.unwrap();
-    assert!(Vote::get_reaction_by_id::<User, Item>(&mut txn, "r3")
+    let item = Item(2000);
+    Star(200)
+        .react(&mut txn, "r1", &User(1000), &item)
         .await
-        .is_err());
+        .unwrap();
+    assert!(matches!(
+        Star(100).react(&mut txn, "r2", &User(1001), &item).await,
+        Err(Error::Overflow {
+            reaction_type: "Star"
+        })
+    ));
+    txn.rollback().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_before_store() {
+    check_before_store(MemoryStore::default()).await;
+}
+
+async fn check_before_store(mut store: impl Store) {
+    let mut txn = store.begin_txn().await.unwrap();
+
+    let user = User(1000);
+    let item = Item(2000);
+
+    assert_eq!(
+        Rate(5).react(&mut txn, "r1", &user, &item).await.unwrap(),
+        None
+    );
+    assert!(matches!(
+        Rate(4).react(&mut txn, "r2", &user, &item).await,
+        Err(Error::Conflict { rid, .. }) if rid == "r1"
+    ));
+    assert_eq!(Rate::get_rid(&mut txn, &user, &item).await.unwrap(), "r1");
+
+    // lookup errors other than not found are propagated
+    let key = to_key(&UserItemToReactionOnceKeyRef {
+        reaction_type: TypeName::<Vote>::new(),
+        user: &user,
+        item: &item,
+    })
+    .unwrap();
+    txn.put(&key, b"corrupt").await.unwrap();
+    assert!(matches!(
+        Vote(1).react(&mut txn, "r3", &user, &item).await,
+        Err(Error::CorruptData { key: k, .. }) if k == key
+    ));
+    assert!(matches!(
+        Vote::get_reaction_by_id::<User, Item>(&mut txn, "r3").await,
+        Err(Error::NotFound { .. })
+    ));
+}
+
+#[tokio::test]
+async fn test_failed_react() {
+    check_failed_react(MemoryStore::default()).await;
+}
+
+async fn check_failed_react(mut store: impl Store) {
+    let user = User(1000);
+    let item = Item(2000);
+
+    // corrupt aggregate fails reacting after reaction info is stored
+    let key = to_key(&ItemAggregateKeyRef {
+        reaction_type: TypeName::<Vote>::new(),
+        item: &item,
+    })
+    .unwrap();
+    let mut txn = store.begin_txn().await.unwrap();
+    txn.put(&key, b"corrupt").await.unwrap();
+    txn.commit().await.unwrap();
+
+    let mut txn = store.begin_txn().await.unwrap();
+    assert!(matches!(
+        Vote(1).react(&mut txn, "r1", &user, &item).await,
+        Err(Error::CorruptData { key: k, .. }) if k == key
+    ));
+    txn.rollback().await.unwrap();
+    Vote(1)
+        .react(&mut txn, "r2", &user, &Item(2001))
+        .await
+        .unwrap();
+    assert!(Vote(1).react(&mut txn, "r3", &user, &item).await.is_err());
+    drop(txn);
+
+    // no orphan keys are left behind, besides the format version recorded by
+    // persistent stores
+    let mut txn = store.begin_txn().await.unwrap();
+    assert!(matches!(
+        Vote::get_reaction_by_id::<User, Item>(&mut txn, "r1").await,
+        Err(Error::NotFound { .. })
+    ));
+    assert!(matches!(
+        Vote::get_rid(&mut txn, &user, &item).await,
+        Err(Error::NotFound { .. })
+    ));
+    assert_eq!(
+        txn.scan_keys(b"", &[0xFF], usize::MAX)
+            .await
+            .unwrap()
+            .filter(|k| !k.starts_with(b"FormatVersionKey"))
+            .collect::<Vec<_>>(),
+        vec![key]
+    );
+}
+
+#[tokio::test]
+async fn test_value_codecs() {
+    for codec in [
+        Codec::Json,
+        #[cfg(feature = "bincode")]
+        Codec::Bincode,
+        #[cfg(feature = "msgpack")]
+        Codec::MessagePack,
+    ] {
+        let mut store = MemoryStore::with_codec(codec);
+        let mut txn = store.begin_txn().await.unwrap();
+        assert_eq!(txn.codec(), codec);
+
+        let user = User(1000);
+        let item = Item(2000);
+        Vote(1).react(&mut txn, "r1", &user, &item).await.unwrap();
+        Vote(-1).react(&mut txn, "r2", &user, &item).await.unwrap();
+        Tag("rust".to_string())
+            .react(&mut txn, "r3", &user, &item)
+            .await
+            .unwrap();
+
+        let r = Vote::get_reaction_by_id::<User, Item>(&mut txn, "r2")
+            .await
+            .unwrap();
+        assert!(r.reaction == Vote(-1));
+        assert_eq!(Vote::get_rid(&mut txn, &user, &item).await.unwrap(), "r2");
+        assert_eq!(
+            Vote::get_item_aggregate(&mut txn, &item).await.unwrap(),
+            Aggregate { sum: -1, count: 1 }
+        );
+        assert_eq!(
+            Tag("rust".to_string())
+                .get_unique_rid(&mut txn, &user, &item)
+                .await
+                .unwrap(),
+            "r3"
+        );
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_concurrent_reactors() {
+    check_concurrent_reactors(MemoryStore::default()).await;
+}
+
+async fn check_concurrent_reactors<TS: Store + Clone + 'static>(store: TS) {
+    let item = Item(2000);
+
+    let tasks: Vec<_> = (0..10)
+        .map(|i| {
+            let mut store = store.clone();
+            let item = item.clone();
+            tokio::spawn(async move {
+                let mut txn = store.begin_txn().await.unwrap();
+                let vote = Vote(if i % 2 == 0 { 1 } else { -1 });
+                vote.react(&mut txn, &format!("r{i}"), &User(i), &item)
+                    .await
+                    .unwrap();
+                tokio::task::yield_now().await;
+                txn.commit().await.unwrap();
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    let mut store = store;
+    let mut txn = store.begin_txn().await.unwrap();
+    assert_eq!(
+        Vote::get_item_aggregate(&mut txn, &item).await.unwrap(),
+        Aggregate { sum: 0, count: 10 }
+    );
+    assert_eq!(Vote(1).count_by_value(&mut txn, &item).await.unwrap(), 5);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_concurrent_once_reactors() {
+    check_concurrent_once_reactors(MemoryStore::default()).await;
+}
+
+async fn check_concurrent_once_reactors<TS: Store + Clone + 'static>(store: TS) {
+    let user = User(1000);
+    let item = Item(2000);
+
+    // interleaved votes of the same user, each read the aggregate before
+    // locking the user-item pair, and are retried if it changed meanwhile
+    let tasks: Vec<_> = (0..4)
+        .map(|i| {
+            let mut store = store.clone();
+            let (user, item) = (user.clone(), item.clone());
+            tokio::spawn(async move {
+                loop {
+                    let mut txn = store.begin_txn().await.unwrap();
+                    let result = async {
+                        Vote::get_item_aggregate(&mut txn, &item).await?;
+                        tokio::task::yield_now().await;
+                        Vote(i)
+                            .react(&mut txn, &format!("r{i}"), &user, &item)
+                            .await?;
+                        tokio::task::yield_now().await;
+                        txn.commit().await
+                    }
+                    .await;
+                    match result {
+                        Ok(()) => break,
+                        Err(Error::TransactionConflict) => txn.rollback().await.unwrap(),
+                        Err(e) => panic!("{e:?}"),
+                    }
+                }
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    let mut store = store;
+    let mut txn = store.begin_txn().await.unwrap();
     let rid = Vote::get_rid(&mut txn, &user, &item).await.unwrap();
-    assert!(&rid == "r4");
+    let vote = Vote::get_reaction_by_id::<User, Item>(&mut txn, &rid)
+        .await
+        .unwrap()
+        .reaction;
+    assert_eq!(
+        Vote::get_item_aggregate(&mut txn, &item).await.unwrap(),
+        Aggregate {
+            sum: vote.0,
+            count: 1
+        }
+    );
+    let mut count = 0;
+    for i in 0..4 {
+        count += Vote(i).count_by_value(&mut txn, &item).await.unwrap();
+    }
+    assert_eq!(count, 1);
+}
+
+/// Run the checks of reactors above on stores made by `new_store`, a fresh one
+/// for each check.
+#[cfg(any(feature = "redb", feature = "sqlite"))]
+pub(crate) async fn check_reactors<TS, Fut>(new_store: impl Fn() -> Fut)
+where
+    TS: Store + Clone + 'static,
+    Fut: std::future::Future<Output = TS>,
+{
+    check_reaction_info(new_store().await).await;
+    check_unique_index(new_store().await).await;
+    check_enum_index(new_store().await).await;
+    check_numerical_aggregate(new_store().await).await;
+    check_numerical_aggregate_by_item_type(new_store().await).await;
+    check_numerical_aggregate_overflow(new_store().await).await;
+    check_before_store(new_store().await).await;
+    check_failed_react(new_store().await).await;
+    check_concurrent_reactors(new_store().await).await;
+    check_concurrent_once_reactors(new_store().await).await;
 }
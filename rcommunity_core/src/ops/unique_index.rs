@@ -1,8 +1,44 @@
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 
-use crate::{error::Result, store::Transaction};
+use crate::{
+    error::{Error, Result},
+    markers::{ItemType, ReactionType, UserType, ID},
+    store::{
+        format::{tagged, to_key, typename, TypeName},
+        Transaction,
+    },
+};
 
-use crate::markers::{ItemType, ReactionType, UserType, ID};
+use super::decode_value;
+
+#[derive(Serialize)]
+#[serde(rename = "UniqueIndexKey")]
+pub struct UniqueIndexKeyRef<'a, TU, TI, TR>
+where
+    TU: UserType,
+    TI: ItemType,
+    TR: ReactionType,
+{
+    pub reaction_type: TypeName<TR>,
+    #[serde(serialize_with = "tagged::serialize::<TU, _, _>")]
+    pub user: &'a TU,
+    #[serde(serialize_with = "tagged::serialize::<TI, _, _>")]
+    pub item: &'a TI,
+    #[serde(serialize_with = "tagged::serialize::<TR, _, _>")]
+    pub reaction: &'a TR,
+}
+
+#[derive(Serialize)]
+#[serde(rename = "UniqueIndexValue")]
+pub(crate) struct UniqueIndexValueRef<'a> {
+    pub rid: &'a str,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct UniqueIndexValue {
+    pub rid: String,
+}
 
 #[async_trait]
 pub trait UniqueIndex {
@@ -46,41 +82,97 @@ impl<T: ReactionType> UniqueIndex for T {
     }
 }
 
+/// Specialized [`UniqueIndex`] implementor for reaction types that are marked
+/// as [`ID`].
+///
+/// Under the hood, this implementor manages **user-item-reaction triplet** to
+/// **reaction ID** mapping, so that the same reaction can be made at most once
+/// for each user-item pair.
 #[async_trait]
 impl<T: ReactionType + ID> UniqueIndex for T {
     async fn store_unique_index(
         &self,
-        _txn: &mut impl Transaction,
+        txn: &mut impl Transaction,
+        rid: &str,
+        user: &impl UserType,
+        item: &impl ItemType,
+    ) -> Result<()> {
+        let key = to_key(&UniqueIndexKeyRef {
+            reaction_type: TypeName::<T>::new(),
+            user,
+            item,
+            reaction: self,
+        })?;
+        if let Some(v) = txn.get_for_update(&key).await? {
+            let v: UniqueIndexValue = decode_value(&key, &v)?;
+            return Err(Error::Conflict {
+                reaction_type: typename::<T>(),
+                rid: v.rid,
+            });
+        }
+        let value = UniqueIndexValueRef { rid };
+        txn.put(&key, &txn.codec().to_value(&value)?).await?;
+        Ok(())
+    }
+    async fn discard_unique_index(
+        &self,
+        txn: &mut impl Transaction,
         _rid: &str,
-        _user: &impl UserType,
-        _item: &impl ItemType,
+        user: &impl UserType,
+        item: &impl ItemType,
     ) -> Result<()> {
-        //     let typename = typename::<T>();
-        //     let key = format!(
-        //         "u_{typename}_{}_{}_{}",
-        //         Serializable::serialize(user),
-        //         Serializable::serialize(item),
-        //         Serializable::serialize(self)
-        //     );
-        //     txn.put(key.as_bytes(), rid.as_bytes()).await?;
-        //     Ok(())
-        // }
-        // async fn discard_unique_index(
-        //     &self,
-        //     txn: &mut impl Transaction,
-        //     _rid: &str,
-        //     user: &impl UserType,
-        //     item: &impl ItemType,
-        // ) -> Result<()> {
-        //     let typename = typename::<T>();
-        //     let key = format!(
-        //         "u_{typename}_{}_{}_{}",
-        //         Serializable::serialize(user),
-        //         Serializable::serialize(item),
-        //         Serializable::serialize(self)
-        //     );
-        //     txn.delete(key.as_bytes()).await?;
-        //     Ok(())
-        todo!()
+        let key = UniqueIndexKeyRef {
+            reaction_type: TypeName::<T>::new(),
+            user,
+            item,
+            reaction: self,
+        };
+        txn.delete(&to_key(&key)?).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+pub trait UniqueIndexLookup: ReactionType {
+    async fn get_unique_rid<TU, TI>(
+        &self,
+        txn: &mut impl Transaction,
+        user: &TU,
+        item: &TI,
+    ) -> Result<String>
+    where
+        TU: UserType,
+        TI: ItemType;
+}
+
+#[async_trait]
+impl<T: ReactionType + ID> UniqueIndexLookup for T {
+    async fn get_unique_rid<TU, TI>(
+        &self,
+        txn: &mut impl Transaction,
+        user: &TU,
+        item: &TI,
+    ) -> Result<String>
+    where
+        TU: UserType,
+        TI: ItemType,
+    {
+        let key = UniqueIndexKeyRef {
+            reaction_type: TypeName::<T>::new(),
+            user,
+            item,
+            reaction: self,
+        };
+        let key = to_key(&key)?;
+        let value = txn.get(&key).await?;
+        if let Some(v) = value {
+            let v: UniqueIndexValue = decode_value(&key, &v)?;
+            Ok(v.rid)
+        } else {
+            Err(Error::NotFound {
+                reaction_type: typename::<T>(),
+                rid: None,
+            })
+        }
     }
 }
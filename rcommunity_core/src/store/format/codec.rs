@@ -0,0 +1,158 @@
+//! Codecs of store values.
+//!
+//! Each encoded value starts with the tag byte of its codec, so that values
+//! written with different codecs can be read back regardless of the codec
+//! currently selected, e.g. while migrating a store from one codec to another.
+
+use serde::{Deserialize, Serialize};
+
+use super::error::{Error, Result};
+
+/// Serialization format of store values.
+pub trait ValueCodec {
+    /// Tag byte prepended to values encoded by this codec.
+    const TAG: u8;
+
+    /// Serialize value, without tag byte.
+    ///
+    /// # Errors
+    /// Will return `Err` if value cannot be serialized by this codec.
+    fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>>;
+
+    /// Deserialize value, without tag byte.
+    ///
+    /// # Errors
+    /// Will return `Err` if value cannot be deserialized by this codec.
+    fn decode<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T>;
+}
+
+/// JSON codec, the default.
+pub struct Json;
+
+impl ValueCodec for Json {
+    const TAG: u8 = 0x01;
+
+    fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(value)?)
+    }
+
+    fn decode<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+/// [bincode](https://docs.rs/bincode) codec, compact and fast but not
+/// self-describing.
+#[cfg(feature = "bincode")]
+pub struct Bincode;
+
+#[cfg(feature = "bincode")]
+impl ValueCodec for Bincode {
+    const TAG: u8 = 0x02;
+
+    fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(value)?)
+    }
+
+    fn decode<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+}
+
+/// [MessagePack](https://msgpack.org) codec, with structs encoded as arrays.
+#[cfg(feature = "msgpack")]
+pub struct MessagePack;
+
+#[cfg(feature = "msgpack")]
+impl ValueCodec for MessagePack {
+    const TAG: u8 = 0x03;
+
+    fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+        Ok(rmp_serde::to_vec(value)?)
+    }
+
+    fn decode<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T> {
+        Ok(rmp_serde::from_slice(bytes)?)
+    }
+}
+
+/// Codec selected at runtime, e.g. by a store.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum Codec {
+    #[default]
+    Json,
+    #[cfg(feature = "bincode")]
+    Bincode,
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
+impl Codec {
+    /// Tag byte of this codec.
+    #[must_use]
+    pub fn tag(self) -> u8 {
+        match self {
+            Codec::Json => Json::TAG,
+            #[cfg(feature = "bincode")]
+            Codec::Bincode => Bincode::TAG,
+            #[cfg(feature = "msgpack")]
+            Codec::MessagePack => MessagePack::TAG,
+        }
+    }
+
+    /// Codec of tag byte, if it is enabled.
+    #[must_use]
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            Json::TAG => Some(Codec::Json),
+            #[cfg(feature = "bincode")]
+            Bincode::TAG => Some(Codec::Bincode),
+            #[cfg(feature = "msgpack")]
+            MessagePack::TAG => Some(Codec::MessagePack),
+            _ => None,
+        }
+    }
+
+    /// Serialize value with this codec, prepended with its tag byte.
+    ///
+    /// # Errors
+    /// Will return `Err` if value cannot be serialized by this codec.
+    pub fn to_value<T: Serialize + ?Sized>(self, value: &T) -> Result<Vec<u8>> {
+        let mut bytes = vec![self.tag()];
+        bytes.extend(match self {
+            Codec::Json => Json::encode(value)?,
+            #[cfg(feature = "bincode")]
+            Codec::Bincode => Bincode::encode(value)?,
+            #[cfg(feature = "msgpack")]
+            Codec::MessagePack => MessagePack::encode(value)?,
+        });
+        Ok(bytes)
+    }
+
+    fn decode<'a, T: Deserialize<'a>>(self, bytes: &'a [u8]) -> Result<T> {
+        match self {
+            Codec::Json => Json::decode(bytes),
+            #[cfg(feature = "bincode")]
+            Codec::Bincode => Bincode::decode(bytes),
+            #[cfg(feature = "msgpack")]
+            Codec::MessagePack => MessagePack::decode(bytes),
+        }
+    }
+}
+
+/// Deserialize value written by any enabled codec, as selected by its tag
+/// byte.
+///
+/// # Errors
+/// Will return `Err` if value is written by an unknown or disabled codec, or
+/// cannot be deserialized properly.
+pub fn from_value<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T> {
+    let (&tag, rest) = bytes.split_first().ok_or(Error::UnexpectedEnd)?;
+    match Codec::from_tag(tag) {
+        Some(codec) => codec.decode(rest),
+        // values written before tags were introduced are untagged JSON, which
+        // never starts with a control byte
+        None if !tag.is_ascii_control() || tag.is_ascii_whitespace() => Json::decode(bytes),
+        None => Err(Error::UnknownCodec(tag)),
+    }
+}
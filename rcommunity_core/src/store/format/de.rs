@@ -1,9 +1,12 @@
 use std::io::Cursor;
 
 use byteorder::{BigEndian, ReadBytesExt};
-use serde::de::{self, SeqAccess, Visitor};
+use serde::de::{self, EnumAccess, IntoDeserializer, SeqAccess, VariantAccess, Visitor};
 
-use super::error::{Error, Result};
+use super::{
+    error::{Error, Result},
+    ser::{ESCAPE, ESCAPED, INT_ZERO, NONE, SEQ_ELEMENT, SEQ_END, SOME, TERMINATOR},
+};
 
 pub struct Deserializer<'de> {
     input: &'de [u8],
@@ -15,34 +18,81 @@ impl<'de> Deserializer<'de> {
     }
 }
 
+#[allow(clippy::elidable_lifetime_names)]
 impl<'de> Deserializer<'de> {
     fn peek(&mut self) -> Result<u8> {
         let v = self.input.iter().next().ok_or(Error::UnexpectedEnd)?;
         Ok(*v)
     }
 
+    /// Consume `expected` from input, e.g. a type name or a separator.
+    fn expect(&mut self, expected: &[u8]) -> Result<()> {
+        if self.input.len() < expected.len() {
+            return Err(Error::UnexpectedEnd);
+        }
+        if !self.input.starts_with(expected) {
+            return Err(Error::InvalidEncoding);
+        }
+        self.input = &self.input[expected.len()..];
+        Ok(())
+    }
+
     fn parse_bool(&mut self) -> Result<bool> {
-        if self.peek()? == b't' {
-            self.input = &self.input[b"true".len()..];
-            Ok(true)
+        let v = self.peek()?;
+        self.input = &self.input[1..];
+        match v {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::InvalidEncoding),
+        }
+    }
+
+    /// Parse a tagged integer, as `Err` of its value if negative, or `Ok` of
+    /// its value if non-negative.
+    fn parse_integer(&mut self) -> Result<core::result::Result<u128, i128>> {
+        let tag = self.peek()?;
+        let negative = tag < INT_ZERO;
+        let len = if negative {
+            INT_ZERO - 1 - tag
         } else {
-            self.input = &self.input[b"false".len()..];
-            Ok(false)
+            tag - INT_ZERO
+        } as usize;
+        if len > 16 {
+            return Err(Error::InvalidEncoding);
+        }
+        if self.input.len() < len + 1 {
+            return Err(Error::UnexpectedEnd);
+        }
+        let mut bytes = [0; 16];
+        bytes[16 - len..].copy_from_slice(&self.input[1..=len]);
+        self.input = &self.input[len + 1..];
+        if !negative {
+            return Ok(Ok(u128::from_be_bytes(bytes)));
         }
+        // bytes of negative integers are those of !v complemented
+        for b in &mut bytes[16 - len..] {
+            *b = !*b;
+        }
+        let v = i128::try_from(u128::from_be_bytes(bytes)).map_err(|_| Error::InvalidEncoding)?;
+        Ok(Err(!v))
     }
 
-    fn parse_signed(&mut self) -> Result<i64> {
-        let mut rdr = Cursor::new(&self.input[..8]);
-        let v = rdr
-            .read_u64::<BigEndian>()
-            .map_err(|_| Error::UnexpectedEnd)?;
-        self.input = &self.input[8..];
-        #[allow(clippy::cast_possible_wrap)]
-        Ok((v ^ (1 << 63)) as i64)
+    fn parse_signed(&mut self) -> Result<i128> {
+        match self.parse_integer()? {
+            Ok(v) => Ok(v.try_into()?),
+            Err(v) => Ok(v),
+        }
+    }
+
+    fn parse_unsigned(&mut self) -> Result<u128> {
+        match self.parse_integer()? {
+            Ok(v) => Ok(v),
+            Err(v) => Ok(v.try_into()?),
+        }
     }
 
     fn parse_float(&mut self) -> Result<f64> {
-        let mut rdr = Cursor::new(&self.input[..8]);
+        let mut rdr = Cursor::new(self.input);
         let v = rdr
             .read_u64::<BigEndian>()
             .map_err(|_| Error::UnexpectedEnd)?;
@@ -52,19 +102,42 @@ impl<'de> Deserializer<'de> {
         Ok(f64::from_bits(v ^ mask))
     }
 
-    fn parse_bytes(&mut self) -> Vec<u8> {
+    fn parse_char(&mut self) -> Result<char> {
+        let mut rdr = Cursor::new(self.input);
+        let v = rdr
+            .read_u32::<BigEndian>()
+            .map_err(|_| Error::UnexpectedEnd)?;
+        self.input = &self.input[4..];
+        char::from_u32(v).ok_or(Error::InvalidEncoding)
+    }
+
+    fn parse_bytes(&mut self) -> Result<Vec<u8>> {
         let mut bytes = Vec::new();
-        for v in self.input.iter() {
-            if v == &b':' || v == &b'_' {
-                break;
+        let mut iter = self.input.iter().enumerate();
+        while let Some((_, &v)) = iter.next() {
+            if v != ESCAPE {
+                bytes.push(v);
+                continue;
+            }
+            match iter.next() {
+                Some((_, &ESCAPED)) => bytes.push(ESCAPE),
+                Some((i, &TERMINATOR)) => {
+                    self.input = &self.input[i + 1..];
+                    return Ok(bytes);
+                }
+                Some(_) => return Err(Error::InvalidEncoding),
+                None => break,
             }
-            bytes.push(*v);
         }
-        self.input = &self.input[bytes.len()..];
-        bytes
+        Err(Error::UnexpectedEnd)
+    }
+
+    fn parse_string(&mut self) -> Result<String> {
+        Ok(String::from_utf8(self.parse_bytes()?)?)
     }
 }
 
+#[allow(clippy::needless_lifetimes)]
 impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     type Error = Error;
 
@@ -107,35 +180,49 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        visitor.visit_i64(self.parse_signed()?)
+        visitor.visit_i64(self.parse_signed()?.try_into()?)
+    }
+
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_i128(self.parse_signed()?)
     }
 
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_u8(self.parse_signed()?.try_into()?)
+        visitor.visit_u8(self.parse_unsigned()?.try_into()?)
     }
 
     fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_u16(self.parse_signed()?.try_into()?)
+        visitor.visit_u16(self.parse_unsigned()?.try_into()?)
     }
 
     fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_u32(self.parse_signed()?.try_into()?)
+        visitor.visit_u32(self.parse_unsigned()?.try_into()?)
     }
 
     fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_u64(self.parse_signed()?.try_into()?)
+        visitor.visit_u64(self.parse_unsigned()?.try_into()?)
+    }
+
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_u128(self.parse_unsigned()?)
     }
 
     fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
@@ -153,46 +240,52 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         visitor.visit_f64(self.parse_float()?)
     }
 
-    fn deserialize_char<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        Err(Error::NotSupported)
+        visitor.visit_char(self.parse_char()?)
     }
 
     fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_str(&String::from_utf8_lossy(&self.parse_bytes()))
+        visitor.visit_str(&self.parse_string()?)
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_string(String::from_utf8_lossy(&self.parse_bytes()).to_string())
+        visitor.visit_string(self.parse_string()?)
     }
 
     fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_bytes(&self.parse_bytes())
+        visitor.visit_bytes(&self.parse_bytes()?)
     }
 
     fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_byte_buf(self.parse_bytes())
+        visitor.visit_byte_buf(self.parse_bytes()?)
     }
 
-    fn deserialize_option<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        let tag = self.peek()?;
+        self.input = &self.input[1..];
+        match tag {
+            NONE => visitor.visit_none(),
+            SOME => visitor.visit_some(self),
+            _ => Err(Error::InvalidEncoding),
+        }
     }
 
     fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
@@ -206,104 +299,320 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        todo!()
+        Err(Error::NotSupported)
     }
 
     fn deserialize_newtype_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        // remove type name and comma
-        self.input = &self.input[name.len() + 1..];
+        self.expect(name.as_bytes())?;
+        self.expect(b":")?;
         visitor.visit_newtype_struct(self)
     }
 
-    fn deserialize_seq<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        visitor.visit_seq(Elements { de: self })
     }
 
-    fn deserialize_tuple<V>(self, _len: usize, _visitor: V) -> Result<V::Value>
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        visitor.visit_seq(Fields {
+            de: self,
+            remaining: len,
+            separator: None,
+        })
     }
 
     fn deserialize_tuple_struct<V>(
         self,
-        _name: &'static str,
-        _len: usize,
-        _visitor: V,
+        name: &'static str,
+        len: usize,
+        visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        self.expect(name.as_bytes())?;
+        visitor.visit_seq(Fields {
+            de: self,
+            remaining: len,
+            separator: Some(b'_'),
+        })
     }
 
     fn deserialize_map<V>(self, _visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        Err(Error::NotSupported)
     }
 
     fn deserialize_struct<V>(
         self,
         name: &'static str,
-        _fields: &'static [&'static str],
+        fields: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        // skip name
-        self.input = &self.input[name.len()..];
-        visitor.visit_seq(self)
+        self.expect(name.as_bytes())?;
+        visitor.visit_seq(Fields {
+            de: self,
+            remaining: fields.len(),
+            separator: Some(b'_'),
+        })
     }
 
     fn deserialize_enum<V>(
         self,
         _name: &'static str,
         _variants: &'static [&'static str],
-        _visitor: V,
+        visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        visitor.visit_enum(Variant { de: self })
     }
 
     fn deserialize_identifier<V>(self, _visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        Err(Error::NotSupported)
     }
 
     fn deserialize_ignored_any<V>(self, _visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        todo!()
+        Err(Error::NotSupported)
     }
 }
 
-impl<'de> SeqAccess<'de> for Deserializer<'de> {
+/// Deserializer expecting `tag` in place of the name of the outermost struct
+/// of a value, see [`tagged`](super::tagged).
+pub(crate) struct Renamed<'a, 'de> {
+    pub(crate) de: &'a mut Deserializer<'de>,
+    pub(crate) tag: &'static str,
+}
+
+/// Forward deserialization to the inner deserializer.
+macro_rules! forward_deserialize {
+    ($($method:ident),* $(,)?) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value>
+            where
+                V: Visitor<'de>,
+            {
+                self.de.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Renamed<'_, 'de> {
+    type Error = Error;
+
+    forward_deserialize!(
+        deserialize_any,
+        deserialize_bool,
+        deserialize_i8,
+        deserialize_i16,
+        deserialize_i32,
+        deserialize_i64,
+        deserialize_i128,
+        deserialize_u8,
+        deserialize_u16,
+        deserialize_u32,
+        deserialize_u64,
+        deserialize_u128,
+        deserialize_f32,
+        deserialize_f64,
+        deserialize_char,
+        deserialize_str,
+        deserialize_string,
+        deserialize_bytes,
+        deserialize_byte_buf,
+        deserialize_option,
+        deserialize_unit,
+        deserialize_seq,
+        deserialize_map,
+        deserialize_identifier,
+        deserialize_ignored_any,
+    );
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.de.deserialize_unit_struct(self.tag, visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.de.deserialize_newtype_struct(self.tag, visitor)
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.de.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.de.deserialize_tuple_struct(self.tag, len, visitor)
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.de.deserialize_struct(self.tag, fields, visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.de.deserialize_enum(name, variants, visitor)
+    }
+}
+
+/// Fixed number of fields of structs, tuple structs and tuples, each preceded
+/// by an optional separator.
+struct Fields<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    remaining: usize,
+    separator: Option<u8>,
+}
+
+impl<'de> SeqAccess<'de> for Fields<'_, 'de> {
     type Error = Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
     where
         T: de::DeserializeSeed<'de>,
     {
-        if self.input.is_empty() {
+        if self.remaining == 0 {
             return Ok(None);
         }
-        // skip leading underscore
-        self.input = &self.input[1..];
-        seed.deserialize(self).map(Some)
+        self.remaining -= 1;
+        if let Some(separator) = self.separator {
+            self.de.expect(&[separator])?;
+        }
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+/// Elements of a sequence, each marked by [`SEQ_ELEMENT`] and ended with
+/// [`SEQ_END`].
+struct Elements<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+}
+
+impl<'de> SeqAccess<'de> for Elements<'_, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        let marker = self.de.peek()?;
+        self.de.input = &self.de.input[1..];
+        match marker {
+            SEQ_ELEMENT => seed.deserialize(&mut *self.de).map(Some),
+            SEQ_END => Ok(None),
+            _ => Err(Error::InvalidEncoding),
+        }
+    }
+}
+
+/// Enum variant, identified by its index.
+struct Variant<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+}
+
+impl<'de> EnumAccess<'de> for Variant<'_, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let index: u32 = self.de.parse_unsigned()?.try_into()?;
+        let v = seed.deserialize(IntoDeserializer::<Error>::into_deserializer(index))?;
+        Ok((v, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Variant<'_, 'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        self.de.expect(b":")?;
+        seed.deserialize(self.de)
+    }
+
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(Fields {
+            de: self.de,
+            remaining: len,
+            separator: Some(b'_'),
+        })
+    }
+
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_seq(Fields {
+            de: self.de,
+            remaining: fields.len(),
+            separator: Some(b'_'),
+        })
     }
 }
@@ -12,10 +12,40 @@ pub enum Error {
     UnknownError(String),
     #[error("Encountered end-of-string unexpectedly.")]
     UnexpectedEnd,
+    #[error("Invalid encoding in key.")]
+    InvalidEncoding,
+    #[error("Invalid UTF-8 string in key: {0}.")]
+    Utf8Error(#[from] std::string::FromUtf8Error),
     #[error("Serialization not supported for type.")]
     NotSupported,
+    #[error("Type tag `{tag}` of `{name}` is already used by `{registered}`.")]
+    DuplicateTag {
+        tag: &'static str,
+        registered: &'static str,
+        name: &'static str,
+    },
+    #[error("Unknown or disabled value codec with tag {0:#04x}.")]
+    UnknownCodec(u8),
     #[error("serde_json error: {0}.")]
     JsonError(#[from] serde_json::Error),
+    #[cfg(feature = "bincode")]
+    #[error("bincode error: {0}.")]
+    BincodeError(#[from] bincode::Error),
+    #[cfg(feature = "msgpack")]
+    #[error("MessagePack encode error: {0}.")]
+    MessagePackEncodeError(#[from] rmp_serde::encode::Error),
+    #[cfg(feature = "msgpack")]
+    #[error("MessagePack decode error: {0}.")]
+    MessagePackDecodeError(#[from] rmp_serde::decode::Error),
+}
+
+/// Error deserializing a key, along with the key.
+#[derive(Error, Debug)]
+#[error("Invalid key {key:?}: {source}.")]
+pub struct KeyError {
+    pub key: Vec<u8>,
+    #[source]
+    pub source: Error,
 }
 
 impl ser::Error for Error {
@@ -1,17 +1,23 @@
 //! Store format types to help enforce consistent data se/desrialization.
 
+pub mod codec;
 pub mod error;
 mod ser;
 mod de;
+pub mod tagged;
 
 #[cfg(test)]
 mod tests;
 
-use std::{any::type_name, marker::PhantomData};
+use std::{any::type_name, collections::BTreeMap, marker::PhantomData};
 
-use serde::{de::Visitor, Deserialize, Serialize};
+use serde::{
+    de::{DeserializeOwned, Visitor},
+    Deserialize, Serialize,
+};
 
-use error::Result;
+pub use codec::Codec;
+use error::{Error, KeyError, Result};
 
 /// Serialize object to store key.
 ///
@@ -36,15 +42,109 @@ where
     Ok(t)
 }
 
-/// Serialize object to store value.
+/// Serialize a partially filled key structure to the range of all keys
+/// starting with it, as `(start, end)` with `end` exclusive, e.g. for
+/// [`Transaction::scan`](super::Transaction::scan).
+///
+/// Fields to be left open are given as `()` or [`Placeholder`] as the last
+/// filled ones of the prefix structure, which is renamed to the serde name of
+/// the full key structure:
+///
+/// ```
+/// # use rcommunity_core::store::format::{from_keys, to_key, to_key_range};
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Serialize, Deserialize)]
+/// struct IndexKey {
+///     user: String,
+///     rid: String,
+/// }
+///
+/// #[derive(Serialize)]
+/// #[serde(rename = "IndexKey")]
+/// struct IndexPrefix<'a> {
+///     user: &'a str,
+///     rid: (),
+/// }
+///
+/// let key = |user: &str, rid: &str| {
+///     to_key(&IndexKey { user: user.into(), rid: rid.into() }).unwrap()
+/// };
+/// let (start, end) = to_key_range(&IndexPrefix { user: "a", rid: () }).unwrap();
+/// assert!(start <= key("a", "") && key("a", "r1") < end);
+/// assert!(key("ab", "r1") >= end);
+///
+/// let keys: Vec<IndexKey> = from_keys(vec![key("a", "r1")])
+///     .collect::<Result<_, _>>()
+///     .unwrap();
+/// assert_eq!(keys[0].rid, "r1");
+/// ```
+///
+/// # Errors
+/// Will return `Err` if value is not serializable as store key.
+pub fn to_key_range<T: Serialize + ?Sized>(prefix: &T) -> Result<(Vec<u8>, Vec<u8>)> {
+    let start = to_key(prefix)?;
+    let end = prefix_end(&start);
+    Ok((start, end))
+}
+
+/// Smallest key greater than all keys starting with `prefix`. Keys always
+/// start with a type name, so such a key exists.
+fn prefix_end(prefix: &[u8]) -> Vec<u8> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            return end;
+        }
+    }
+    end
+}
+
+/// Iterator deserializing keys with [`from_key`], see [`from_keys`].
+pub struct KeyIter<T, I> {
+    keys: I,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T, I> Iterator for KeyIter<T, I>
+where
+    T: DeserializeOwned,
+    I: Iterator<Item = Vec<u8>>,
+{
+    type Item = std::result::Result<T, KeyError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let key = self.keys.next()?;
+        Some(from_key(&key).map_err(|source| KeyError { key, source }))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.keys.size_hint()
+    }
+}
+
+/// Deserialize key structures from scanned keys, e.g. from
+/// [`Transaction::scan_keys`](super::Transaction::scan_keys).
+pub fn from_keys<T, I>(keys: I) -> KeyIter<T, I::IntoIter>
+where
+    T: DeserializeOwned,
+    I: IntoIterator<Item = Vec<u8>>,
+{
+    KeyIter {
+        keys: keys.into_iter(),
+        phantom: PhantomData,
+    }
+}
+
+/// Serialize object to store value with the default [`Codec`].
 ///
 /// # Errors
 /// Will return `Err` if value is not serialized properly.
 pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
-    Ok(serde_json::to_vec(value)?)
+    Codec::default().to_value(value)
 }
 
-/// Deserialize value from bytes.
+/// Deserialize value from bytes, written by any enabled [`Codec`].
 ///
 /// # Errors
 /// Will return `Err` if value cannot be deserialized properly.
@@ -52,7 +152,7 @@ pub fn from_value<'a, T>(s: &'a [u8]) -> Result<T>
 where
     T: Deserialize<'a>,
 {
-    Ok(serde_json::from_slice(s)?)
+    codec::from_value(s)
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -163,24 +263,101 @@ struct TypeNameVisitor<T: ?Sized> {
     phantom: PhantomData<T>,
 }
 
-impl<'de, T: ?Sized> Visitor<'de> for TypeNameVisitor<T> {
+impl<T: ?Sized> Visitor<'_> for TypeNameVisitor<T> {
     type Value = TypeName<T>;
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
         formatter.write_str("typename")
     }
 
-    fn visit_borrowed_str<E>(self, _v: &'de str) -> std::result::Result<Self::Value, E>
+    fn visit_str<E>(self, v: &str) -> std::result::Result<Self::Value, E>
     where
         E: serde::de::Error,
     {
+        if v != typename::<T>() {
+            return Err(E::invalid_value(serde::de::Unexpected::Str(v), &self));
+        }
         Ok(TypeName {
             phantom: PhantomData,
         })
     }
 }
 
-fn typename<T: ?Sized>() -> &'static str {
-    let full_type_name = type_name::<T>();
-    full_type_name.split("::").last().unwrap_or(full_type_name)
+/// Stable tag identifying a type in store keys.
+///
+/// Implemented by the derives when given `#[rcommunity(tag = "...")]`. Types
+/// without a tag fall back to their type name, which changes when the type is
+/// renamed and is shared by types of the same name in different modules.
+pub trait TypeTag {
+    const TAG: &'static str;
+}
+
+trait TagOf {
+    fn tag() -> &'static str;
+    fn type_tag() -> Option<&'static str>;
+}
+
+impl<T: ?Sized> TagOf for T {
+    default fn tag() -> &'static str {
+        let full_type_name = type_name::<T>();
+        full_type_name.split("::").last().unwrap_or(full_type_name)
+    }
+    default fn type_tag() -> Option<&'static str> {
+        None
+    }
+}
+
+impl<T: ?Sized + TypeTag> TagOf for T {
+    fn tag() -> &'static str {
+        T::TAG
+    }
+    fn type_tag() -> Option<&'static str> {
+        Some(T::TAG)
+    }
+}
+
+/// Name of type `T` as written in store keys, i.e. its [`TypeTag`] if any, or
+/// the last segment of its type name otherwise.
+#[must_use]
+pub fn typename<T: ?Sized>() -> &'static str {
+    <T as TagOf>::tag()
+}
+
+/// Registry of type tags in use, to detect types sharing a keyspace.
+#[derive(Debug, Default)]
+pub struct TagRegistry {
+    types: BTreeMap<&'static str, &'static str>,
+}
+
+impl TagRegistry {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the tag of type `T`.
+    ///
+    /// # Errors
+    /// Will return `Err` if the tag is already registered by another type.
+    pub fn register<T: ?Sized>(&mut self) -> Result<()> {
+        let tag = typename::<T>();
+        let name = type_name::<T>();
+        match self.types.get(tag) {
+            Some(&registered) if registered != name => Err(Error::DuplicateTag {
+                tag,
+                registered,
+                name,
+            }),
+            _ => {
+                self.types.insert(tag, name);
+                Ok(())
+            }
+        }
+    }
+
+    /// Type name registered with `tag`.
+    #[must_use]
+    pub fn get(&self, tag: &str) -> Option<&'static str> {
+        self.types.get(tag).copied()
+    }
 }
@@ -3,11 +3,32 @@ use serde::{ser, Serialize};
 
 use super::error::{Error, Result};
 
+/// Byte starting an escape sequence in encoded strings.
+pub(crate) const ESCAPE: u8 = 0x00;
+/// Byte following [`ESCAPE`] to represent a literal `0x00`.
+pub(crate) const ESCAPED: u8 = 0xFF;
+/// Byte following [`ESCAPE`] to mark the end of a string.
+pub(crate) const TERMINATOR: u8 = 0x01;
+/// Byte preceding each element of a sequence.
+pub(crate) const SEQ_ELEMENT: u8 = 0x01;
+/// Byte marking the end of a sequence, sorting shorter sequences first.
+pub(crate) const SEQ_END: u8 = 0x00;
+/// Byte representing `None`, sorting before any `Some`.
+pub(crate) const NONE: u8 = 0x00;
+/// Byte preceding the value of `Some`.
+pub(crate) const SOME: u8 = 0x01;
+/// Tag of integer zero. Non-negative integers are tagged with this plus the
+/// length of their big-endian bytes, and negative integers with this minus one
+/// minus the length of their complemented bytes, so that integers of any width
+/// compare by value.
+pub(crate) const INT_ZERO: u8 = 0x80;
+
 /// Custom serializer that encode object as store key.
 pub struct Serializer {
     pub(crate) output: Vec<u8>,
 }
 
+#[allow(clippy::needless_lifetimes)]
 impl<'a> ser::Serializer for &'a mut Serializer {
     type Ok = ();
 
@@ -22,41 +43,55 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     type SerializeStructVariant = Self;
 
     fn serialize_bool(self, v: bool) -> Result<()> {
-        if v {
-            self.serialize_str("true")
-        } else {
-            self.serialize_str("false")
-        }
+        self.output.push(u8::from(v));
+        Ok(())
     }
 
     fn serialize_i8(self, v: i8) -> Result<()> {
-        self.serialize_i64(i64::from(v))
+        self.serialize_i128(i128::from(v))
     }
     fn serialize_i16(self, v: i16) -> Result<()> {
-        self.serialize_i64(i64::from(v))
+        self.serialize_i128(i128::from(v))
     }
     fn serialize_i32(self, v: i32) -> Result<()> {
-        self.serialize_i64(i64::from(v))
+        self.serialize_i128(i128::from(v))
     }
     fn serialize_i64(self, v: i64) -> Result<()> {
-        // equivalent as v + 2^63
-        #[allow(clippy::cast_sign_loss)]
-        let v = (v as u64) ^ (1 << 63);
-        self.output.write_u64::<BigEndian>(v).unwrap(); // write to Vec will never fail
-        Ok(())
+        self.serialize_i128(i128::from(v))
+    }
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        if v < 0 {
+            // !v is non-negative, and decreases as v increases
+            #[allow(clippy::cast_sign_loss)]
+            let bytes = significant_bytes(!v as u128);
+            #[allow(clippy::cast_possible_truncation)]
+            self.output.push(INT_ZERO - 1 - bytes.len() as u8);
+            self.output.extend(bytes.iter().map(|b| !b));
+            Ok(())
+        } else {
+            #[allow(clippy::cast_sign_loss)]
+            self.serialize_u128(v as u128)
+        }
     }
 
     fn serialize_u8(self, v: u8) -> Result<()> {
-        self.serialize_i64(v.into())
+        self.serialize_u128(v.into())
     }
     fn serialize_u16(self, v: u16) -> Result<()> {
-        self.serialize_i64(v.into())
+        self.serialize_u128(v.into())
     }
     fn serialize_u32(self, v: u32) -> Result<()> {
-        self.serialize_i64(v.into())
+        self.serialize_u128(v.into())
     }
     fn serialize_u64(self, v: u64) -> Result<()> {
-        self.serialize_i64(v.try_into()?)
+        self.serialize_u128(v.into())
+    }
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        let bytes = significant_bytes(v);
+        #[allow(clippy::cast_possible_truncation)]
+        self.output.push(INT_ZERO + bytes.len() as u8);
+        self.output.extend(bytes);
+        Ok(())
     }
 
     fn serialize_f32(self, v: f32) -> Result<()> {
@@ -70,23 +105,35 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         Ok(())
     }
 
-    fn serialize_char(self, _v: char) -> Result<()> {
-        Err(Error::NotSupported)
+    fn serialize_char(self, v: char) -> Result<()> {
+        // code points compare in the same order as chars
+        self.output.write_u32::<BigEndian>(v.into()).unwrap(); // write to Vec will never fail
+        Ok(())
     }
     fn serialize_str(self, v: &str) -> Result<()> {
-        self.output.extend(v.as_bytes());
-        Ok(())
+        self.serialize_bytes(v.as_bytes())
     }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<()> {
-        self.output.extend(v);
+        // escape 0x00 as 0x00 0xFF and terminate with 0x00 0x01, so that
+        // encoded strings never contain their terminator and still compare
+        // in the same order as the original bytes
+        for &b in v {
+            self.output.push(b);
+            if b == ESCAPE {
+                self.output.push(ESCAPED);
+            }
+        }
+        self.output.extend([ESCAPE, TERMINATOR]);
         Ok(())
     }
 
     fn serialize_none(self) -> Result<()> {
+        self.output.push(NONE);
         Ok(())
     }
     fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<()> {
+        self.output.push(SOME);
         value.serialize(self)
     }
 
@@ -96,13 +143,15 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
         Err(Error::NotSupported)
     }
+    // Variants are encoded by index instead of name, so that they are ordered
+    // as declared.
     fn serialize_unit_variant(
         self,
         _name: &'static str,
-        _variant_index: u32,
+        variant_index: u32,
         _variant: &'static str,
     ) -> Result<()> {
-        Err(Error::NotSupported)
+        self.serialize_u32(variant_index)
     }
 
     fn serialize_newtype_struct<T: ?Sized + Serialize>(
@@ -118,130 +167,291 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     fn serialize_newtype_variant<T: ?Sized + Serialize>(
         self,
         _name: &'static str,
-        _variant_index: u32,
+        variant_index: u32,
         _variant: &'static str,
-        _value: &T,
+        value: &T,
     ) -> Result<()> {
-        todo!()
+        self.serialize_u32(variant_index)?;
+        self.output.push(b':');
+        value.serialize(self)
     }
 
     fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
-        todo!()
+        Ok(self)
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
-        todo!()
+        Ok(self)
     }
 
     fn serialize_tuple_struct(
         self,
-        _name: &'static str,
+        name: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
-        todo!()
+        self.output.extend(name.as_bytes());
+        Ok(self)
     }
 
     fn serialize_tuple_variant(
         self,
         _name: &'static str,
-        _variant_index: u32,
+        variant_index: u32,
         _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        todo!()
+        self.serialize_u32(variant_index)?;
+        Ok(self)
     }
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
-        todo!()
+        Err(Error::NotSupported)
     }
 
     fn serialize_struct(self, name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
-        self.serialize_str(name)?;
+        self.output.extend(name.as_bytes());
         Ok(self)
     }
 
     fn serialize_struct_variant(
         self,
         _name: &'static str,
-        _variant_index: u32,
+        variant_index: u32,
         _variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        todo!()
+        self.serialize_u32(variant_index)?;
+        Ok(self)
     }
 }
 
+/// Serializer writing `tag` in place of the name of the outermost struct of a
+/// value, see [`tagged`](super::tagged).
+pub(crate) struct Renamed<'a> {
+    pub(crate) ser: &'a mut Serializer,
+    pub(crate) tag: &'static str,
+}
+
+/// Forward serialization of primitives to the inner serializer.
+macro_rules! forward_serialize {
+    ($($method:ident($ty:ty)),* $(,)?) => {
+        $(
+            fn $method(self, v: $ty) -> Result<()> {
+                self.ser.$method(v)
+            }
+        )*
+    };
+}
+
+impl<'a> ser::Serializer for Renamed<'a> {
+    type Ok = ();
+
+    type Error = Error;
+
+    type SerializeSeq = &'a mut Serializer;
+    type SerializeTuple = &'a mut Serializer;
+    type SerializeTupleStruct = &'a mut Serializer;
+    type SerializeTupleVariant = &'a mut Serializer;
+    type SerializeMap = &'a mut Serializer;
+    type SerializeStruct = &'a mut Serializer;
+    type SerializeStructVariant = &'a mut Serializer;
+
+    forward_serialize!(
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_i128(i128),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_u128(u128),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+        serialize_bytes(&[u8]),
+    );
+
+    fn serialize_none(self) -> Result<()> {
+        self.ser.serialize_none()
+    }
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<()> {
+        self.ser.serialize_some(value)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        self.ser.serialize_unit()
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        self.ser.serialize_unit_struct(self.tag)
+    }
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.ser
+            .serialize_unit_variant(name, variant_index, variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.ser.serialize_newtype_struct(self.tag, value)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.ser
+            .serialize_newtype_variant(name, variant_index, variant, value)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.ser.serialize_tuple(len)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        self.ser.serialize_seq(len)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.ser.serialize_tuple_struct(self.tag, len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        self.ser
+            .serialize_tuple_variant(name, variant_index, variant, len)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
+        self.ser.serialize_map(len)
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        self.ser.serialize_struct(self.tag, len)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        self.ser
+            .serialize_struct_variant(name, variant_index, variant, len)
+    }
+}
+
+/// Big-endian bytes of `v` without leading zeros.
+fn significant_bytes(v: u128) -> Vec<u8> {
+    let bytes = v.to_be_bytes();
+    bytes[(v.leading_zeros() / 8) as usize..].to_vec()
+}
+
+#[allow(clippy::needless_lifetimes)]
 impl<'a> ser::SerializeSeq for &'a mut Serializer {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_element<T: ?Sized + Serialize>(&mut self, _value: &T) -> Result<()> {
-        todo!()
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.output.push(SEQ_ELEMENT);
+        value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<()> {
-        todo!()
+        self.output.push(SEQ_END);
+        Ok(())
     }
 }
 
-// Same thing but for tuples.
+// Same thing but for tuples, which have fixed length and need no markers.
+#[allow(clippy::needless_lifetimes)]
 impl<'a> ser::SerializeTuple for &'a mut Serializer {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_element<T: ?Sized + Serialize>(&mut self, _value: &T) -> Result<()> {
-        todo!()
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<()> {
-        todo!()
+        Ok(())
     }
 }
 
-// Same thing but for tuple structs.
+// Same thing but for tuple structs, encoded like structs.
+#[allow(clippy::needless_lifetimes)]
 impl<'a> ser::SerializeTupleStruct for &'a mut Serializer {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T: ?Sized + Serialize>(&mut self, _value: &T) -> Result<()> {
-        todo!()
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.output.push(b'_');
+        value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<()> {
-        todo!()
+        Ok(())
     }
 }
 
+#[allow(clippy::needless_lifetimes)]
 impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T: ?Sized + Serialize>(&mut self, _value: &T) -> Result<()> {
-        todo!()
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.output.push(b'_');
+        value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<()> {
-        todo!()
+        Ok(())
     }
 }
 
+// Maps are rejected by `serialize_map`.
+#[allow(clippy::needless_lifetimes)]
 impl<'a> ser::SerializeMap for &'a mut Serializer {
     type Ok = ();
     type Error = Error;
 
     fn serialize_key<T: ?Sized + Serialize>(&mut self, _key: &T) -> Result<()> {
-        todo!()
+        Err(Error::NotSupported)
     }
 
     fn serialize_value<T: ?Sized + Serialize>(&mut self, _value: &T) -> Result<()> {
-        todo!()
+        Err(Error::NotSupported)
     }
 
     fn end(self) -> Result<()> {
-        todo!()
+        Err(Error::NotSupported)
     }
 }
 
+#[allow(clippy::needless_lifetimes)]
 impl<'a> ser::SerializeStruct for &'a mut Serializer {
     type Ok = ();
     type Error = Error;
@@ -260,6 +470,7 @@ impl<'a> ser::SerializeStruct for &'a mut Serializer {
     }
 }
 
+#[allow(clippy::needless_lifetimes)]
 impl<'a> ser::SerializeStructVariant for &'a mut Serializer {
     type Ok = ();
     type Error = Error;
@@ -267,12 +478,13 @@ impl<'a> ser::SerializeStructVariant for &'a mut Serializer {
     fn serialize_field<T: ?Sized + Serialize>(
         &mut self,
         _key: &'static str,
-        _value: &T,
+        value: &T,
     ) -> Result<()> {
-        todo!()
+        self.output.push(b'_');
+        value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<()> {
-        todo!()
+        Ok(())
     }
 }
@@ -0,0 +1,118 @@
+//! Se/deserialization of key components under their [`TypeTag`], in place
+//! of their serde names.
+//!
+//! Structs write their serde names in keys, so that keys of different types
+//! are told apart. For types with a [`TypeTag`], the tag is written instead,
+//! so that keys are kept when the type is renamed or moved, and types of the
+//! same name do not collide. Key structures opt in for their user, item and
+//! reaction fields:
+//!
+//! ```
+//! # use rcommunity_core::store::format::{tagged, to_key, TypeTag};
+//! # use serde::Serialize;
+//! #[derive(Serialize)]
+//! struct Renamed(u32);
+//! impl TypeTag for Renamed {
+//!     const TAG: &'static str = "Item";
+//! }
+//!
+//! #[derive(Serialize)]
+//! struct Item(u32);
+//!
+//! #[derive(Serialize)]
+//! struct Key<'a, T: Serialize> {
+//!     #[serde(serialize_with = "tagged::serialize::<T, _, _>")]
+//!     item: &'a T,
+//! }
+//!
+//! assert_eq!(
+//!     to_key(&Key { item: &Renamed(1) }).unwrap(),
+//!     to_key(&Key { item: &Item(1) }).unwrap(),
+//! );
+//! ```
+//!
+//! Other serializers and deserializers are not affected.
+//!
+//! [`TypeTag`]: super::TypeTag
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{de, ser, TagOf};
+
+/// Serialize `value` of type `T`, or a reference to it, under the tag of `T`.
+///
+/// # Errors
+/// Will return `Err` if `value` cannot be serialized.
+pub fn serialize<T, V, S>(value: &V, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: ?Sized,
+    V: Serialize + ?Sized,
+    S: Serializer,
+{
+    match <T as TagOf>::type_tag() {
+        Some(tag) => serializer.serialize_tagged(tag, value),
+        None => value.serialize(serializer),
+    }
+}
+
+/// Deserialize a value of type `T` written by [`serialize`].
+///
+/// # Errors
+/// Will return `Err` if value cannot be deserialized.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    match <T as TagOf>::type_tag() {
+        Some(tag) => deserializer.deserialize_tagged(tag),
+        None => T::deserialize(deserializer),
+    }
+}
+
+trait SerializeTagged: Serializer {
+    fn serialize_tagged<V: Serialize + ?Sized>(
+        self,
+        tag: &'static str,
+        value: &V,
+    ) -> Result<Self::Ok, Self::Error>;
+}
+
+impl<S: Serializer> SerializeTagged for S {
+    default fn serialize_tagged<V: Serialize + ?Sized>(
+        self,
+        _tag: &'static str,
+        value: &V,
+    ) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+}
+
+impl SerializeTagged for &mut ser::Serializer {
+    fn serialize_tagged<V: Serialize + ?Sized>(
+        self,
+        tag: &'static str,
+        value: &V,
+    ) -> Result<Self::Ok, Self::Error> {
+        value.serialize(ser::Renamed { ser: self, tag })
+    }
+}
+
+trait DeserializeTagged<'de>: Deserializer<'de> {
+    fn deserialize_tagged<T: Deserialize<'de>>(self, tag: &'static str) -> Result<T, Self::Error>;
+}
+
+impl<'de, D: Deserializer<'de>> DeserializeTagged<'de> for D {
+    default fn deserialize_tagged<T: Deserialize<'de>>(
+        self,
+        _tag: &'static str,
+    ) -> Result<T, Self::Error> {
+        T::deserialize(self)
+    }
+}
+
+impl<'de> DeserializeTagged<'de> for &mut de::Deserializer<'de> {
+    fn deserialize_tagged<T: Deserialize<'de>>(self, tag: &'static str) -> Result<T, Self::Error> {
+        T::deserialize(de::Renamed { de: self, tag })
+    }
+}
@@ -1,8 +1,14 @@
+use proptest::prelude::*;
 use serde::{de::DeserializeOwned, Serialize};
 
-use super::{from_key, to_key, Placeholder};
+use super::{
+    codec::{Json, ValueCodec},
+    error::Error,
+    from_key, from_keys, from_value, tagged, to_key, to_key_range, to_value, typename, Codec,
+    Placeholder, TagRegistry, TypeName, TypeTag,
+};
 
-#[derive(serde::Serialize, serde::Deserialize, PartialEq)]
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
 struct User(String);
 
 #[derive(serde::Serialize, serde::Deserialize, PartialEq)]
@@ -14,6 +20,9 @@ struct Index {
     item: Item,
 }
 
+#[derive(serde::Deserialize)]
+struct Unit;
+
 #[derive(serde::Serialize, serde::Deserialize, PartialEq)]
 #[serde(rename = "Index")]
 struct Query {
@@ -28,11 +37,112 @@ struct QueryItem {
     item: Placeholder<Item>,
 }
 
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+struct PostId(u64, u32);
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+struct Review(User, String);
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq)]
+struct Nested {
+    index: Index,
+    tags: Vec<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+enum Reaction {
+    Upvote,
+    Downvote,
+    Rate(u8),
+    Tag(String, bool),
+    Reply { parent: User, depth: u32 },
+}
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq)]
+struct ReactionIndex {
+    item: Item,
+    reaction: Reaction,
+    rid: String,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename = "ReactionIndex")]
+struct ReactionIndexPrefix {
+    item: Item,
+    reaction: Reaction,
+    rid: (),
+}
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq)]
+struct CommentKey {
+    item: Item,
+    parent: Option<String>,
+    rid: String,
+}
+
+mod other {
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq)]
+    pub struct User(pub String);
+
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq)]
+    pub struct Tagged;
+
+    impl super::TypeTag for Tagged {
+        const TAG: &'static str = "acme.user";
+    }
+
+    /// A user type tagged `acme.user`, and the same type after renaming.
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    pub struct Account {
+        pub name: String,
+        pub parent: Option<super::User>,
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    pub struct Member {
+        pub name: String,
+        pub parent: Option<super::User>,
+    }
+
+    impl super::TypeTag for Account {
+        const TAG: &'static str = "acme.user";
+    }
+
+    impl super::TypeTag for Member {
+        const TAG: &'static str = "acme.user";
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+#[serde(bound(
+    serialize = "TU: serde::Serialize",
+    deserialize = "TU: serde::Deserialize<'de>"
+))]
+struct TaggedIndex<TU> {
+    #[serde(
+        serialize_with = "tagged::serialize::<TU, _, _>",
+        deserialize_with = "tagged::deserialize"
+    )]
+    user: TU,
+    rid: String,
+}
+
+/// Byte slice serialized as bytes rather than as a sequence.
+struct Bytes<'a>(&'a [u8]);
+
+impl Serialize for Bytes<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
 #[test]
+#[allow(clippy::manual_assert_eq)]
 fn test_key_serialization() {
-    assert!(to_key("").unwrap() == b"");
+    assert!(to_key("").unwrap() == b"\0\x01");
+    assert!(to_key("a\0b").unwrap() == b"a\0\xffb\0\x01");
     assert!(to_key(&()).unwrap() == b"");
-    assert!(to_key(&User("a".to_string())).unwrap() == b"User:a");
+    assert!(to_key(&User("a".to_string())).unwrap() == b"User:a\0\x01");
     assert!(to_key(&Placeholder::<User>::new()).unwrap() == b"User:");
 
     assert!(
@@ -41,7 +151,7 @@ fn test_key_serialization() {
             item: Item("b".to_string())
         })
         .unwrap()
-            == b"Index_User:a_Item:b"
+            == b"Index_User:a\0\x01_Item:b\0\x01"
     );
     assert!(
         to_key(&Query {
@@ -49,24 +159,47 @@ fn test_key_serialization() {
             item: (),
         })
         .unwrap()
-            == b"Index_User:a_"
+            == b"Index_User:a\0\x01_"
     );
+    assert!(
+        to_key(&Review(User("a".to_string()), "b".to_string())).unwrap()
+            == b"Review_User:a\0\x01_b\0\x01"
+    );
+    assert!(to_key(&("a", "b")).unwrap() == b"a\0\x01b\0\x01");
+    assert!(to_key(&vec!["a", "b"]).unwrap() == b"\x01a\0\x01\x01b\0\x01\0");
     assert!(
         to_key(&QueryItem {
             user: User("a".to_string()),
             item: Placeholder::new()
         })
         .unwrap()
-            == b"Index_User:a_Item:"
+            == b"Index_User:a\0\x01_Item:"
     );
 }
 
 #[test]
+#[allow(clippy::manual_assert_eq)]
 fn test_key_ordering() {
     // strings
     assert!(to_key("").unwrap() < to_key(" ").unwrap());
     assert!(to_key("a").unwrap() < to_key("b").unwrap());
     assert!(to_key("user:a").unwrap() < to_key("user:b").unwrap());
+    assert!(to_key("a").unwrap() < to_key("a\0").unwrap());
+    assert!(to_key("a\0").unwrap() < to_key("a\x01").unwrap());
+    assert!(to_key("a").unwrap() < to_key("ab").unwrap());
+
+    // strings are terminated, so that a key is never a prefix of another
+    let alice = to_key(&Index {
+        user: User("alice".to_string()),
+        item: Item("b".to_string()),
+    })
+    .unwrap();
+    let alice_smith = to_key(&Query {
+        user: User("alice_smith".to_string()),
+        item: (),
+    })
+    .unwrap();
+    assert!(!alice.starts_with(&alice_smith));
 
     // ints
     assert!(to_key(&-3).unwrap() < to_key(&-1).unwrap());
@@ -74,6 +207,50 @@ fn test_key_ordering() {
     assert!(to_key(&-1).unwrap() < to_key(&1u8).unwrap());
     assert!(to_key(&1u32).unwrap() < to_key(&2u8).unwrap());
     assert!(to_key(&2u32).unwrap() == to_key(&2i32).unwrap());
+    assert_eq!(to_key(&-2i8).unwrap(), to_key(&-2i128).unwrap());
+    assert_eq!(to_key(&7u8).unwrap(), to_key(&7u128).unwrap());
+    assert!(to_key(&i64::MAX).unwrap() < to_key(&(i64::MAX as u64 + 1)).unwrap());
+    assert!(to_key(&u64::MAX).unwrap() < to_key(&(u128::from(u64::MAX) + 1)).unwrap());
+    assert!(to_key(&i128::MAX).unwrap() < to_key(&u128::MAX).unwrap());
+    assert!(to_key(&i128::MIN).unwrap() < to_key(&i64::MIN).unwrap());
+    assert!(to_key(&i64::MIN).unwrap() < to_key(&-1i8).unwrap());
+    assert!(to_key(&-256).unwrap() < to_key(&-255).unwrap());
+    assert!(to_key(&255u8).unwrap() < to_key(&256u16).unwrap());
+
+    // tuples and tuple structs compare element by element
+    assert!(to_key(&(1, "b")).unwrap() < to_key(&(2, "a")).unwrap());
+    assert!(to_key(&("a", 2)).unwrap() < to_key(&("ab", 1)).unwrap());
+    assert!(to_key(&PostId(1, 9)).unwrap() < to_key(&PostId(2, 0)).unwrap());
+    assert!(to_key(&PostId(1, 1)).unwrap() < to_key(&PostId(1, 2)).unwrap());
+
+    // sequences compare lexicographically, shorter prefixes first
+    assert!(to_key(&Vec::<u8>::new()).unwrap() < to_key(&vec![0u8]).unwrap());
+    assert!(to_key(&vec![1, 2]).unwrap() < to_key(&vec![1, 2, 0]).unwrap());
+    assert!(to_key(&vec![1, 2, 3]).unwrap() < to_key(&vec![1, 3]).unwrap());
+    assert!(to_key(&vec!["a"]).unwrap() < to_key(&vec!["a", ""]).unwrap());
+
+    // enum variants are ordered by index, then by their content
+    assert!(to_key(&Reaction::Upvote).unwrap() < to_key(&Reaction::Downvote).unwrap());
+    assert!(to_key(&Reaction::Downvote).unwrap() < to_key(&Reaction::Rate(0)).unwrap());
+    assert!(to_key(&Reaction::Rate(1)).unwrap() < to_key(&Reaction::Rate(2)).unwrap());
+    assert!(
+        to_key(&Reaction::Rate(9)).unwrap() < to_key(&Reaction::Tag(String::new(), false)).unwrap()
+    );
+    assert!(
+        to_key(&Reaction::Tag("a".to_string(), true)).unwrap()
+            < to_key(&Reaction::Tag("b".to_string(), false)).unwrap()
+    );
+
+    // none sorts before any some
+    assert!(to_key(&None::<i32>).unwrap() < to_key(&Some(i32::MIN)).unwrap());
+    assert!(to_key(&None::<String>).unwrap() < to_key(&Some(String::new())).unwrap());
+    assert!(to_key(&Some(1)).unwrap() < to_key(&Some(2)).unwrap());
+    assert!(to_key(&(None::<u8>, "z")).unwrap() < to_key(&(Some(0u8), "a")).unwrap());
+
+    // chars
+    assert!(to_key(&'a').unwrap() < to_key(&'b').unwrap());
+    assert!(to_key(&'z').unwrap() < to_key(&'\u{e9}').unwrap());
+    assert!(to_key(&'\u{ffff}').unwrap() < to_key(&'\u{1f600}').unwrap());
 
     // floats
     assert!(to_key(&-1e12).unwrap() < to_key(&-1e-12).unwrap());
@@ -97,6 +274,14 @@ fn test_key_deserialization() {
     assert_recoverable(&1_u32);
     assert_recoverable(&2_u8);
     assert_recoverable(&2_i16);
+    assert_recoverable(&0_u64);
+    assert_recoverable(&u64::MAX);
+    assert_recoverable(&i64::MIN);
+    assert_recoverable(&i64::MAX);
+    assert_recoverable(&u128::MAX);
+    assert_recoverable(&i128::MIN);
+    assert_recoverable(&i128::MAX);
+    assert_recoverable(&PostId(u64::MAX, u32::MAX));
 
     assert_recoverable(&0.1);
     assert_recoverable(&-0.15);
@@ -107,13 +292,68 @@ fn test_key_deserialization() {
     assert_recoverable(&1e18_f32);
     assert_recoverable(&-2.34e-3_f32);
 
+    assert_recoverable(&'a');
+    assert_recoverable(&'\0');
+    assert_recoverable(&'\u{1f600}');
+    assert_recoverable(&char::MAX);
+
     assert_recoverable(&"hello".to_string());
+    assert_recoverable(&"c++:std".to_string());
+    assert_recoverable(&"\0\x01\u{ff}".to_string());
+    assert_recoverable(&User("alice_smith".to_string()));
 
     assert_recoverable(&User("hello".to_string()));
     assert_recoverable(&Item(String::new()));
 
     assert_recoverable(&Placeholder::<User>::new());
 
+    assert_recoverable(&(1, "a".to_string(), true));
+    assert_recoverable(&PostId(1, 2));
+    assert_recoverable(&Review(User("a_b".to_string()), "c:d".to_string()));
+    assert_recoverable(&Vec::<String>::new());
+    assert_recoverable(&vec![3, -1, 2]);
+    assert_recoverable(&vec![vec![String::new()], vec![]]);
+    assert_recoverable(&None::<i32>);
+    assert_recoverable(&Some(3));
+    assert_recoverable(&Some(Some(String::new())));
+    assert_recoverable(&Some(None::<String>));
+    assert_recoverable(&vec![None, Some('x')]);
+    assert_recoverable(&CommentKey {
+        item: Item("b".to_string()),
+        parent: None,
+        rid: "r1".to_string(),
+    });
+    assert_recoverable(&CommentKey {
+        item: Item("b".to_string()),
+        parent: Some("r0".to_string()),
+        rid: "r1".to_string(),
+    });
+
+    assert_recoverable(&Reaction::Upvote);
+    assert_recoverable(&Reaction::Downvote);
+    assert_recoverable(&Reaction::Rate(5));
+    assert_recoverable(&Reaction::Tag("c++:std".to_string(), true));
+    assert_recoverable(&Reaction::Reply {
+        parent: User("a".to_string()),
+        depth: 3,
+    });
+    assert_recoverable(&vec![Reaction::Downvote, Reaction::Rate(1)]);
+    assert_recoverable(&ReactionIndex {
+        item: Item("b".to_string()),
+        reaction: Reaction::Reply {
+            parent: User("a".to_string()),
+            depth: 1,
+        },
+        rid: "r1".to_string(),
+    });
+    assert_recoverable(&Nested {
+        index: Index {
+            user: User("a".to_string()),
+            item: Item("b".to_string()),
+        },
+        tags: vec!["x".to_string(), "y".to_string()],
+    });
+
     assert_recoverable(&Index {
         user: User("a".to_string()),
         item: Item("b".to_string()),
@@ -127,3 +367,354 @@ fn test_key_deserialization() {
         item: Placeholder::new(),
     });
 }
+
+#[test]
+fn test_type_tag() {
+    assert_eq!(typename::<User>(), "User");
+    assert_eq!(typename::<other::User>(), "User");
+    assert_eq!(typename::<other::Tagged>(), other::Tagged::TAG);
+
+    assert_eq!(
+        to_key(&TypeName::<other::Tagged>::new()).unwrap(),
+        b"acme.user\0\x01"
+    );
+    assert_eq!(
+        to_key(&Placeholder::<other::Tagged>::new()).unwrap(),
+        b"acme.user:"
+    );
+    assert_recoverable(&TypeName::<other::Tagged>::new());
+    assert!(
+        from_key::<TypeName<other::Tagged>>(&to_key(&TypeName::<User>::new()).unwrap()).is_err()
+    );
+
+    let mut registry = TagRegistry::new();
+    registry.register::<User>().unwrap();
+    registry.register::<User>().unwrap();
+    registry.register::<other::Tagged>().unwrap();
+    assert!(registry.register::<other::User>().is_err());
+    assert_eq!(registry.get("User"), Some(std::any::type_name::<User>()));
+}
+
+#[test]
+fn test_tagged_key() {
+    let account = TaggedIndex {
+        user: other::Account {
+            name: "alice".to_string(),
+            parent: Some(User("bob".to_string())),
+        },
+        rid: "r1".to_string(),
+    };
+    let member = TaggedIndex {
+        user: other::Member {
+            name: "alice".to_string(),
+            parent: Some(User("bob".to_string())),
+        },
+        rid: "r1".to_string(),
+    };
+
+    // renamed types keep their keys, with nested types untouched
+    let key = to_key(&account).unwrap();
+    assert_eq!(key, to_key(&member).unwrap());
+    assert!(key.starts_with(b"TaggedIndex_acme.user_"));
+    assert!(key.windows(5).any(|w| w == b"User:"));
+    assert_eq!(
+        from_key::<TaggedIndex<other::Member>>(&key).unwrap(),
+        member
+    );
+    assert_recoverable(&account);
+
+    // while untagged types still write their names
+    assert!(to_key(&TaggedIndex {
+        user: User("alice".to_string()),
+        rid: "r1".to_string(),
+    })
+    .unwrap()
+    .starts_with(b"TaggedIndex_User:"));
+    assert!(from_key::<TaggedIndex<User>>(&key).is_err());
+
+    // and other serializers are not affected
+    assert_eq!(
+        serde_json::to_string(&account).unwrap(),
+        r#"{"user":{"name":"alice","parent":"bob"},"rid":"r1"}"#
+    );
+}
+
+#[test]
+fn test_enum_prefix() {
+    let key = |reaction: Reaction, rid: &str| {
+        to_key(&ReactionIndex {
+            item: Item("x".to_string()),
+            reaction,
+            rid: rid.to_string(),
+        })
+        .unwrap()
+    };
+    let prefix = to_key(&ReactionIndexPrefix {
+        item: Item("x".to_string()),
+        reaction: Reaction::Upvote,
+        rid: (),
+    })
+    .unwrap();
+
+    // all upvotes of an item, and nothing else, fall under the prefix
+    assert!(key(Reaction::Upvote, "r1").starts_with(&prefix));
+    assert!(key(Reaction::Upvote, "r2").starts_with(&prefix));
+    assert!(!key(Reaction::Downvote, "r1").starts_with(&prefix));
+    assert!(!key(Reaction::Rate(0), "r1").starts_with(&prefix));
+}
+
+#[test]
+fn test_key_range() {
+    let index = |user: &str, item: &str| {
+        to_key(&Index {
+            user: User(user.to_string()),
+            item: Item(item.to_string()),
+        })
+        .unwrap()
+    };
+    let keys = vec![
+        index("a", ""),
+        index("a", "x"),
+        index("a\0", "x"),
+        index("ab", "x"),
+        index("b", "x"),
+    ];
+
+    let in_range = |(start, end): (Vec<u8>, Vec<u8>)| {
+        keys.iter()
+            .filter(|k| **k >= start && **k < end)
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    let range = to_key_range(&Query {
+        user: User("a".to_string()),
+        item: (),
+    })
+    .unwrap();
+    assert_eq!(in_range(range), keys[..2]);
+    let range = to_key_range(&QueryItem {
+        user: User("a".to_string()),
+        item: Placeholder::new(),
+    })
+    .unwrap();
+    assert_eq!(in_range(range), keys[..2]);
+
+    let decoded: Vec<Index> = from_keys(keys.clone()).collect::<Result<_, _>>().unwrap();
+    assert_eq!(decoded[2].user, User("a\0".to_string()));
+    assert_eq!(decoded[4].item.0, "x");
+
+    let mut keys = from_keys::<Index, _>(vec![keys[0].clone(), b"Index_".to_vec()]);
+    assert!(keys.next().unwrap().is_ok());
+    assert_eq!(keys.next().unwrap().err().unwrap().key, b"Index_");
+    assert!(keys.next().is_none());
+}
+
+#[test]
+fn test_key_invalid_encoding() {
+    assert!(from_key::<String>(b"abc").is_err());
+    assert!(from_key::<String>(b"abc\0").is_err());
+    assert!(from_key::<String>(b"abc\0\x02").is_err());
+    assert!(from_key::<String>(b"\xff\0\x01").is_err());
+    assert!(from_key::<User>(b"Usr:a\0\x01").is_err());
+    assert!(from_key::<Vec<bool>>(b"\x01\x01").is_err());
+    assert!(from_key::<Vec<bool>>(b"\x02").is_err());
+    assert!(from_key::<Reaction>(&to_key(&9u32).unwrap()).is_err());
+    assert!(from_key::<Option<bool>>(b"\x02").is_err());
+    assert!(from_key::<u8>(&to_key(&256).unwrap()).is_err());
+    assert!(from_key::<u64>(&to_key(&-1).unwrap()).is_err());
+    assert!(from_key::<i64>(&to_key(&u64::MAX).unwrap()).is_err());
+    assert!(from_key::<u128>(b"\x91").is_err());
+    assert!(from_key::<u32>(b"\x82\x01").is_err());
+    assert!(from_key::<char>(b"\0\0").is_err());
+    assert!(from_key::<char>(b"\0\0\xd8\0").is_err());
+    assert!(to_key(&std::collections::BTreeMap::from([(1, 2)])).is_err());
+
+    assert!(matches!(from_key::<Unit>(b""), Err(Error::NotSupported)));
+    assert!(matches!(
+        from_key::<serde::de::IgnoredAny>(b"abc"),
+        Err(Error::NotSupported)
+    ));
+}
+
+proptest! {
+    #[test]
+    fn prop_string_recoverable(a in ".*", b in ".*") {
+        let v: Index = from_key(&to_key(&Index {
+            user: User(a.clone()),
+            item: Item(b.clone()),
+        }).unwrap()).unwrap();
+        prop_assert_eq!(v.user.0, a);
+        prop_assert_eq!(v.item.0, b);
+    }
+
+    #[test]
+    fn prop_string_ordering(a in ".*", b in ".*") {
+        prop_assert_eq!(
+            to_key(&User(a.clone())).unwrap().cmp(&to_key(&User(b.clone())).unwrap()),
+            a.cmp(&b)
+        );
+    }
+
+    #[test]
+    fn prop_bytes_ordering(
+        a in prop::collection::vec(any::<u8>(), 0..16),
+        b in prop::collection::vec(any::<u8>(), 0..16),
+    ) {
+        let ka = to_key(&Bytes(&a)).unwrap();
+        let kb = to_key(&Bytes(&b)).unwrap();
+        prop_assert_eq!(ka.cmp(&kb), a.cmp(&b));
+    }
+
+    #[test]
+    fn prop_tuple_ordering(a in any::<(u32, String)>(), b in any::<(u32, String)>()) {
+        prop_assert_eq!(to_key(&a).unwrap().cmp(&to_key(&b).unwrap()), a.cmp(&b));
+    }
+
+    #[test]
+    fn prop_tuple_struct_ordering(a in any::<(String, String)>(), b in any::<(String, String)>()) {
+        let ka = to_key(&Review(User(a.0.clone()), a.1.clone())).unwrap();
+        let kb = to_key(&Review(User(b.0.clone()), b.1.clone())).unwrap();
+        prop_assert_eq!(ka.cmp(&kb), a.cmp(&b));
+    }
+
+    #[test]
+    fn prop_int_ordering(a in any::<i128>(), b in any::<i128>()) {
+        prop_assert_eq!(to_key(&a).unwrap().cmp(&to_key(&b).unwrap()), a.cmp(&b));
+        prop_assert_eq!(from_key::<i128>(&to_key(&a).unwrap()).unwrap(), a);
+    }
+
+    #[test]
+    fn prop_uint_ordering(a in any::<u128>(), b in any::<u128>()) {
+        prop_assert_eq!(to_key(&a).unwrap().cmp(&to_key(&b).unwrap()), a.cmp(&b));
+        prop_assert_eq!(from_key::<u128>(&to_key(&a).unwrap()).unwrap(), a);
+    }
+
+    #[test]
+    fn prop_int_cross_width(a in any::<i64>(), b in any::<u64>()) {
+        prop_assert_eq!(
+            to_key(&a).unwrap().cmp(&to_key(&b).unwrap()),
+            i128::from(a).cmp(&i128::from(b))
+        );
+        prop_assert_eq!(to_key(&a).unwrap(), to_key(&i128::from(a)).unwrap());
+        prop_assert_eq!(to_key(&b).unwrap(), to_key(&u128::from(b)).unwrap());
+    }
+
+    #[test]
+    fn prop_char_ordering(a in any::<char>(), b in any::<char>()) {
+        prop_assert_eq!(to_key(&a).unwrap().cmp(&to_key(&b).unwrap()), a.cmp(&b));
+        prop_assert_eq!(from_key::<char>(&to_key(&a).unwrap()).unwrap(), a);
+    }
+
+    #[test]
+    fn prop_option_ordering(a in any::<Option<String>>(), b in any::<Option<String>>()) {
+        prop_assert_eq!(to_key(&a).unwrap().cmp(&to_key(&b).unwrap()), a.cmp(&b));
+        prop_assert_eq!(from_key::<Option<String>>(&to_key(&a).unwrap()).unwrap(), a);
+    }
+
+    #[test]
+    fn prop_seq_recoverable(v in prop::collection::vec(".*", 0..8)) {
+        let v_: Vec<String> = from_key(&to_key(&v).unwrap()).unwrap();
+        prop_assert_eq!(v_, v);
+    }
+
+    #[test]
+    fn prop_seq_ordering(
+        a in prop::collection::vec(any::<i32>(), 0..8),
+        b in prop::collection::vec(any::<i32>(), 0..8),
+    ) {
+        prop_assert_eq!(to_key(&a).unwrap().cmp(&to_key(&b).unwrap()), a.cmp(&b));
+    }
+
+    #[test]
+    fn prop_prefix_scan(a in ".*", b in ".*") {
+        // a complete key lies within the range of the prefix of its own user
+        // only
+        let key = to_key(&Index {
+            user: User(a.clone()),
+            item: Item(String::new()),
+        }).unwrap();
+        let prefix = to_key(&Query { user: User(b.clone()), item: () }).unwrap();
+        prop_assert_eq!(key.starts_with(&prefix), a == b);
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+struct Value {
+    user: String,
+    count: u64,
+    tags: Vec<String>,
+}
+
+fn enabled_codecs() -> Vec<Codec> {
+    vec![
+        Codec::Json,
+        #[cfg(feature = "bincode")]
+        Codec::Bincode,
+        #[cfg(feature = "msgpack")]
+        Codec::MessagePack,
+    ]
+}
+
+#[test]
+fn test_value_codecs() {
+    let value = Value {
+        user: "a".to_string(),
+        count: 3,
+        tags: vec!["x".to_string()],
+    };
+
+    let bytes = to_value(&value).unwrap();
+    assert_eq!(bytes[0], Json::TAG);
+    assert_eq!(&bytes[1..], br#"{"user":"a","count":3,"tags":["x"]}"#);
+
+    // values of any enabled codec are read back, e.g. during migration
+    for codec in enabled_codecs() {
+        let bytes = codec.to_value(&value).unwrap();
+        assert_eq!(bytes[0], codec.tag());
+        assert_eq!(Codec::from_tag(codec.tag()), Some(codec));
+        assert_eq!(from_value::<Value>(&bytes).unwrap(), value);
+    }
+
+    // untagged JSON values
+    assert_eq!(
+        from_value::<Value>(br#"{"user":"a","count":3,"tags":["x"]}"#).unwrap(),
+        value
+    );
+    assert_eq!(from_value::<String>(br#" "a""#).unwrap(), "a");
+
+    assert!(matches!(
+        from_value::<Value>(b"\x7fabc"),
+        Err(Error::UnknownCodec(0x7f))
+    ));
+    assert!(matches!(
+        from_value::<Value>(b""),
+        Err(Error::UnexpectedEnd)
+    ));
+}
+
+#[cfg(feature = "bincode")]
+#[test]
+fn test_value_bincode() {
+    let value = Value {
+        user: "a".to_string(),
+        count: 3,
+        tags: vec![],
+    };
+    let bytes = Codec::Bincode.to_value(&value).unwrap();
+    assert!(bytes.len() < to_value(&value).unwrap().len());
+    assert!(from_value::<Value>(&bytes[..bytes.len() - 1]).is_err());
+}
+
+#[cfg(feature = "msgpack")]
+#[test]
+fn test_value_msgpack() {
+    let value = Value {
+        user: "a".to_string(),
+        count: 3,
+        tags: vec![],
+    };
+    let bytes = Codec::MessagePack.to_value(&value).unwrap();
+    // structs are encoded without field names
+    assert!(!bytes.windows(4).any(|w| w == b"user"));
+    assert_eq!(from_value::<Value>(&bytes).unwrap(), value);
+}
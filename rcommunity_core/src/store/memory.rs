@@ -19,64 +19,181 @@
 
 use std::{collections::BTreeMap, sync::Arc};
 
+use async_lock::{Mutex as AsyncMutex, MutexGuardArc};
 use async_trait::async_trait;
-use parking_lot::{Condvar, Mutex, MutexGuard};
+use parking_lot::Mutex;
 
-use crate::error::Result;
+use crate::error::{Error, Result};
 
-use super::{Store, Transaction};
+use super::{format::Codec, Store, Transaction};
 
 type ByteMap = BTreeMap<Vec<u8>, Vec<u8>>;
 
 /// Implementation of an in-memory [`Store`].
 ///
-/// Internally, each operation of `MemoryStore` first obtains a transaction
-/// lock, then locks the mutex protecting the underlying store. The transaction
-/// lock is implemented as a [`Condvar`], checking whether it is currently in
-/// the same transaction (or no transaction at all) before entering.
-#[derive(Debug, Default)]
+/// Committed pairs are kept in a copy-on-write map. Each transaction reads
+/// from a snapshot of it, taken on first read, and buffers its writes until
+/// commit, which applies them at once. Uncommitted writes are discarded on
+/// rollback or drop.
+///
+/// Transactions track their reads, and fail to commit with
+/// [`Error::TransactionConflict`] if any of them is changed by others.
+///
+/// A transaction calling [`Transaction::get_for_update`] takes the transaction
+/// lock, an async-aware mutex, and holds it until committed or dropped. Taking
+/// the lock refreshes the snapshot to the latest committed state, failing with
+/// [`Error::TransactionConflict`] if any earlier read is changed by then.
+/// Commits of other transactions wait for the lock, yielding to the runtime
+/// instead of blocking the thread.
+///
+/// Transactions begun by [`Store::begin_optimistic_txn`] take no lock, and
+/// only find out about conflicts on commit.
+#[derive(Debug, Default, Clone)]
 pub struct MemoryStore {
-    store: Arc<Mutex<ByteMap>>,
-    cur_txn_id: Arc<(Mutex<usize>, Condvar)>,
-    max_txn_id: usize,
+    store: Arc<Mutex<Arc<ByteMap>>>,
+    txn_lock: Arc<AsyncMutex<()>>,
+    codec: Codec,
+}
+
+impl MemoryStore {
+    /// Create a store writing values with `codec`.
+    #[must_use]
+    pub fn with_codec(codec: Codec) -> Self {
+        Self {
+            codec,
+            ..Self::default()
+        }
+    }
 }
 
 #[async_trait]
 impl Store for MemoryStore {
     type Transaction = MemoryTransaction;
     async fn begin_txn(&mut self) -> Result<MemoryTransaction> {
-        self.max_txn_id += 1;
         Ok(MemoryTransaction {
             store: self.store.clone(),
-            cur_txn_id: self.cur_txn_id.clone(),
-            id: self.max_txn_id,
+            txn_lock: self.txn_lock.clone(),
+            guard: None,
+            snapshot: Mutex::new(None),
+            writes: BTreeMap::new(),
+            reads: Mutex::default(),
+            optimistic: false,
+            codec: self.codec,
         })
     }
+    async fn begin_optimistic_txn(&mut self) -> Result<MemoryTransaction> {
+        let mut txn = self.begin_txn().await?;
+        txn.optimistic = true;
+        Ok(txn)
+    }
+}
+
+/// Reads of a transaction, along with their results.
+#[derive(Default)]
+struct ReadSet {
+    keys: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+    ranges: Vec<RangeRead>,
+}
+
+/// A range scanned by a transaction.
+struct RangeRead {
+    start: Vec<u8>,
+    end: Vec<u8>,
+    limit: usize,
+    reverse: bool,
+    pairs: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl ReadSet {
+    /// Whether all reads return the same results from `store`.
+    fn is_valid(&self, store: &ByteMap) -> bool {
+        self.keys.iter().all(|(k, v)| store.get(k) == v.as_ref())
+            && self.ranges.iter().all(|r| {
+                let pairs = r.pairs.iter().map(|(k, v)| (k, v));
+                let range = store.range(r.start.clone()..r.end.clone());
+                if r.reverse {
+                    range.rev().take(r.limit).eq(pairs)
+                } else {
+                    range.take(r.limit).eq(pairs)
+                }
+            })
+    }
 }
 
 /// Transaction type for [`MemoryStore`].
 pub struct MemoryTransaction {
-    store: Arc<Mutex<ByteMap>>,
-    cur_txn_id: Arc<(Mutex<usize>, Condvar)>,
-    id: usize,
+    store: Arc<Mutex<Arc<ByteMap>>>,
+    txn_lock: Arc<AsyncMutex<()>>,
+    /// Transaction lock, if held by this transaction.
+    guard: Option<MutexGuardArc<()>>,
+    /// Committed pairs as of the first read.
+    snapshot: Mutex<Option<Arc<ByteMap>>>,
+    /// Buffered writes, with `None` for deletion.
+    writes: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+    /// Reads from the snapshot, checked again on commit.
+    reads: Mutex<ReadSet>,
+    /// Whether to take no lock in [`Transaction::get_for_update`].
+    optimistic: bool,
+    codec: Codec,
 }
 
 impl MemoryTransaction {
-    fn txn_lock(&self) -> (MutexGuard<usize>, &Condvar) {
-        let &(ref lock, ref cvar) = &*self.cur_txn_id;
-        let mut cur_txn_id = lock.lock();
-        if *cur_txn_id != 0 && *cur_txn_id != self.id {
-            cvar.wait(&mut cur_txn_id);
+    fn snapshot(&self) -> Arc<ByteMap> {
+        self.snapshot
+            .lock()
+            .get_or_insert_with(|| self.store.lock().clone())
+            .clone()
+    }
+
+    /// Pairs within range as seen by this transaction, in descending key
+    /// order if `reverse`.
+    fn range(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        limit: usize,
+        reverse: bool,
+    ) -> Vec<(Vec<u8>, Vec<u8>)> {
+        if start >= end {
+            return Vec::new();
         }
-        (cur_txn_id, cvar)
-    }
-    fn release_txn_lock(&self) {
-        let &(ref lock, ref cvar) = &*self.cur_txn_id;
-        let mut cur_txn_id = lock.lock();
-        if *cur_txn_id == self.id {
-            *cur_txn_id = 0;
-            cvar.notify_one();
+        let snapshot = self.snapshot();
+        let writes = self.writes.range(start.to_vec()..end.to_vec());
+        // leave room for pairs deleted in this transaction
+        let limit_read = limit.saturating_add(writes.clone().count());
+        let read = snapshot.range(start.to_vec()..end.to_vec());
+        let read: Vec<_> = if reverse {
+            read.rev().take(limit_read).collect()
+        } else {
+            read.take(limit_read).collect()
+        };
+        self.reads.lock().ranges.push(RangeRead {
+            start: start.to_vec(),
+            end: end.to_vec(),
+            limit: limit_read,
+            reverse,
+            pairs: read.iter().map(|&(k, v)| (k.clone(), v.clone())).collect(),
+        });
+        let mut pairs: BTreeMap<_, _> = read.into_iter().map(|(k, v)| (k, Some(v))).collect();
+        for (k, v) in writes {
+            pairs.insert(k, v.as_ref());
         }
+        let pairs = pairs
+            .into_iter()
+            .filter_map(|(k, v)| Some((k.clone(), v?.clone())));
+        if reverse {
+            pairs.rev().take(limit).collect()
+        } else {
+            pairs.take(limit).collect()
+        }
+    }
+
+    /// End this transaction, releasing the transaction lock.
+    fn reset(&mut self) {
+        self.writes.clear();
+        *self.snapshot.get_mut() = None;
+        *self.reads.get_mut() = ReadSet::default();
+        self.guard = None;
     }
 }
 
@@ -86,92 +203,126 @@ impl Transaction for MemoryTransaction {
     type PairIterator = std::vec::IntoIter<(Vec<u8>, Vec<u8>)>;
 
     async fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
-        let (cur_txn_id, cvar) = self.txn_lock();
-        let value = self.store.lock().get(key).cloned();
-        if *cur_txn_id == 0 {
-            cvar.notify_one();
+        if let Some(value) = self.writes.get(key) {
+            return Ok(value.clone());
         }
+        let value = self.snapshot().get(key).cloned();
+        self.reads
+            .lock()
+            .keys
+            .entry(key.to_vec())
+            .or_insert_with(|| value.clone());
         Ok(value)
     }
 
     async fn get_for_update(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>> {
-        let (mut cur_txn_id, _) = self.txn_lock();
-        *cur_txn_id = self.id;
-        Ok(self.store.lock().get(key).cloned())
+        if self.guard.is_none() && !self.optimistic {
+            let guard = self.txn_lock.lock_arc().await;
+            let store = self.store.lock().clone();
+            if !self.reads.get_mut().is_valid(&store) {
+                return Err(Error::TransactionConflict);
+            }
+            self.guard = Some(guard);
+            // read latest values of the pairs to update, which are the same
+            // as before for pairs read already
+            *self.snapshot.get_mut() = Some(store);
+        }
+        self.get(key).await
     }
 
     async fn put(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
-        let (cur_txn_id, cvar) = self.txn_lock();
-        self.store.lock().insert(key.to_vec(), value.to_vec());
-        if *cur_txn_id == 0 {
-            cvar.notify_one();
-        }
+        self.writes.insert(key.to_vec(), Some(value.to_vec()));
         Ok(())
     }
 
     async fn scan(&self, start: &[u8], end: &[u8], limit: usize) -> Result<Self::PairIterator> {
-        let (cur_txn_id, cvar) = self.txn_lock();
-        // needs collect here to pass across async boundary
-        #[allow(clippy::needless_collect)]
-        let value: Vec<(Vec<u8>, Vec<u8>)> = self
-            .store
-            .lock()
-            .range(start.to_vec()..end.to_vec())
-            .take(limit)
-            .map(|(k, v)| (k.clone(), v.clone()))
-            .collect();
-        if *cur_txn_id == 0 {
-            cvar.notify_one();
-        }
-        Ok(value.into_iter())
+        Ok(self.range(start, end, limit, false).into_iter())
     }
 
     async fn scan_keys(&self, start: &[u8], end: &[u8], limit: usize) -> Result<Self::KeyIterator> {
-        let (cur_txn_id, cvar) = self.txn_lock();
         // needs collect here to pass across async boundary
         #[allow(clippy::needless_collect)]
-        let value: Vec<Vec<u8>> = self
-            .store
-            .lock()
-            .range(start.to_vec()..end.to_vec())
-            .take(limit)
-            .map(|(k, _)| k.clone())
+        let keys: Vec<Vec<u8>> = self
+            .range(start, end, limit, false)
+            .into_iter()
+            .map(|(k, _)| k)
             .collect();
-        if *cur_txn_id == 0 {
-            cvar.notify_one();
-        }
-        Ok(value.into_iter())
+        Ok(keys.into_iter())
+    }
+
+    async fn scan_rev(&self, start: &[u8], end: &[u8], limit: usize) -> Result<Self::PairIterator> {
+        Ok(self.range(start, end, limit, true).into_iter())
+    }
+
+    async fn scan_keys_rev(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        limit: usize,
+    ) -> Result<Self::KeyIterator> {
+        let keys: Vec<Vec<u8>> = self
+            .range(start, end, limit, true)
+            .into_iter()
+            .map(|(k, _)| k)
+            .collect();
+        Ok(keys.into_iter())
     }
 
     async fn delete(&mut self, key: &[u8]) -> Result<()> {
-        let (cur_txn_id, cvar) = self.txn_lock();
-        self.store.lock().remove(key);
-        if *cur_txn_id == 0 {
-            cvar.notify_one();
-        }
+        self.writes.insert(key.to_vec(), None);
         Ok(())
     }
 
+    fn codec(&self) -> Codec {
+        self.codec
+    }
+
     async fn commit(&mut self) -> Result<()> {
-        self.release_txn_lock();
+        let mut conflict = false;
+        if !self.writes.is_empty() {
+            // wait for transactions holding the lock to finish
+            let _guard = match self.guard {
+                Some(_) => None,
+                None => Some(self.txn_lock.lock().await),
+            };
+            let mut store = self.store.lock();
+            conflict = !self.reads.lock().is_valid(&store);
+            if !conflict {
+                let store = Arc::make_mut(&mut store);
+                for (key, value) in std::mem::take(&mut self.writes) {
+                    match value {
+                        Some(value) => store.insert(key, value),
+                        None => store.remove(&key),
+                    };
+                }
+            }
+        }
+        self.reset();
+        if conflict {
+            return Err(Error::TransactionConflict);
+        }
         Ok(())
     }
-}
 
-impl Drop for MemoryTransaction {
-    /// Ensure transaction lock is released before dropping.
-    fn drop(&mut self) {
-        self.release_txn_lock();
+    async fn rollback(&mut self) -> Result<()> {
+        self.reset();
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::store::{Store, Transaction};
+    use std::time::Duration;
+
+    use crate::{
+        error::Error,
+        store::{Direction, RetryPolicy, Store, Transaction},
+    };
 
     use super::MemoryStore;
 
     #[tokio::test]
+    #[allow(clippy::manual_assert_eq)]
     async fn test_memory_store() {
         let mut store = MemoryStore::default();
 
@@ -179,6 +330,7 @@ mod test {
         assert!(txn.get(b"key").await.unwrap().is_none());
         txn.put(b"key", b"value").await.unwrap();
         assert_eq!(txn.get(b"key").await.unwrap().unwrap(), b"value");
+        txn.commit().await.unwrap();
 
         let mut txn = store.begin_txn().await.unwrap();
         assert_eq!(txn.get_for_update(b"key").await.unwrap().unwrap(), b"value");
@@ -219,4 +371,299 @@ mod test {
         txn.delete(b"key").await.unwrap();
         assert!(txn.get(b"key").await.unwrap().is_none());
     }
+
+    #[tokio::test]
+    async fn test_memory_store_scan_page() {
+        let mut store = MemoryStore::default();
+        let mut txn = store.begin_txn().await.unwrap();
+        for key in [b"k1", b"k2", b"k3", b"k4"] {
+            txn.put(key, key).await.unwrap();
+        }
+        txn.commit().await.unwrap();
+
+        // reverse scans see writes of this transaction
+        let mut txn = store.begin_txn().await.unwrap();
+        txn.delete(b"k4").await.unwrap();
+        txn.put(b"k5", b"k5").await.unwrap();
+        assert_eq!(
+            txn.scan_rev(b"k", b"l", 2)
+                .await
+                .unwrap()
+                .collect::<Vec<(Vec<u8>, Vec<u8>)>>(),
+            vec![
+                (b"k5".to_vec(), b"k5".to_vec()),
+                (b"k3".to_vec(), b"k3".to_vec())
+            ]
+        );
+        assert_eq!(
+            txn.scan_keys_rev(b"k2", b"k4", 10)
+                .await
+                .unwrap()
+                .collect::<Vec<Vec<u8>>>(),
+            vec![b"k3".to_vec(), b"k2".to_vec()]
+        );
+
+        // pages continue after the cursor in either direction
+        let mut cursor = None;
+        let mut pages = Vec::new();
+        loop {
+            let page: Vec<_> = txn
+                .scan_keys_page(b"k", b"l", cursor.as_deref(), Direction::Forward, 2)
+                .await
+                .unwrap()
+                .collect();
+            if page.is_empty() {
+                break;
+            }
+            cursor = page.last().cloned();
+            pages.push(page);
+        }
+        assert_eq!(
+            pages,
+            vec![
+                vec![b"k1".to_vec(), b"k2".to_vec()],
+                vec![b"k3".to_vec(), b"k5".to_vec()]
+            ]
+        );
+        assert_eq!(
+            txn.scan_page(b"k", b"l", Some(b"k3"), Direction::Backward, 10)
+                .await
+                .unwrap()
+                .collect::<Vec<(Vec<u8>, Vec<u8>)>>(),
+            vec![
+                (b"k2".to_vec(), b"k2".to_vec()),
+                (b"k1".to_vec(), b"k1".to_vec())
+            ]
+        );
+        assert_eq!(
+            txn.scan_keys_page(b"k", b"k3", Some(b"k4"), Direction::Forward, 10)
+                .await
+                .unwrap()
+                .count(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_isolation() {
+        let mut store = MemoryStore::default();
+
+        let mut txn = store.begin_txn().await.unwrap();
+        txn.put(b"key", b"value").await.unwrap();
+        txn.put(b"key2", b"v2").await.unwrap();
+        txn.commit().await.unwrap();
+
+        // uncommitted writes are invisible to others, and reads are isolated
+        // from later commits
+        let mut txn1 = store.begin_txn().await.unwrap();
+        let mut txn2 = store.begin_txn().await.unwrap();
+        txn1.delete(b"key").await.unwrap();
+        txn1.put(b"key3", b"v3").await.unwrap();
+        assert_eq!(txn2.get(b"key").await.unwrap().unwrap(), b"value");
+        assert_eq!(
+            txn1.scan_keys(b"key", b"key4", 10)
+                .await
+                .unwrap()
+                .collect::<Vec<Vec<u8>>>(),
+            vec![b"key2".to_vec(), b"key3".to_vec()]
+        );
+        txn1.commit().await.unwrap();
+        assert_eq!(txn2.get(b"key").await.unwrap().unwrap(), b"value");
+        assert!(txn2.get(b"key3").await.unwrap().is_none());
+        txn2.commit().await.unwrap();
+        assert!(txn2.get(b"key").await.unwrap().is_none());
+
+        // reads changed by others fail taking the lock, or committing
+        let mut txn1 = store.begin_txn().await.unwrap();
+        let mut txn2 = store.begin_txn().await.unwrap();
+        assert_eq!(txn1.get(b"key2").await.unwrap().unwrap(), b"v2");
+        assert_eq!(txn2.get(b"key2").await.unwrap().unwrap(), b"v2");
+        let mut txn = store.begin_txn().await.unwrap();
+        txn.put(b"key2", b"changed").await.unwrap();
+        txn.commit().await.unwrap();
+        assert!(matches!(
+            txn1.get_for_update(b"key3").await,
+            Err(Error::TransactionConflict)
+        ));
+        txn2.put(b"key3", b"v3").await.unwrap();
+        assert!(matches!(
+            txn2.commit().await,
+            Err(Error::TransactionConflict)
+        ));
+        drop(txn1);
+
+        // writes are discarded on rollback or drop
+        let mut txn = store.begin_txn().await.unwrap();
+        txn.put(b"key", b"value").await.unwrap();
+        txn.rollback().await.unwrap();
+        assert!(txn.get(b"key").await.unwrap().is_none());
+        txn.put(b"key", b"value").await.unwrap();
+        drop(txn);
+        let txn = store.begin_txn().await.unwrap();
+        assert!(txn.get(b"key").await.unwrap().is_none());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_memory_store_contention() {
+        let store = MemoryStore::default();
+
+        // contending transactions yield to each other on a single thread
+        let tasks: Vec<_> = (0..4)
+            .map(|_| {
+                let mut store = store.clone();
+                tokio::spawn(async move {
+                    for _ in 0..10 {
+                        let mut txn = store.begin_txn().await.unwrap();
+                        let count = txn
+                            .get_for_update(b"count")
+                            .await
+                            .unwrap()
+                            .map_or(0, |v| v[0]);
+                        tokio::task::yield_now().await;
+                        txn.put(b"count", &[count + 1]).await.unwrap();
+                        txn.commit().await.unwrap();
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        let mut store = store;
+        let mut txn = store.begin_txn().await.unwrap();
+        assert_eq!(txn.get(b"count").await.unwrap().unwrap(), [40]);
+
+        // transaction lock is released on drop
+        txn.get_for_update(b"count").await.unwrap();
+        let mut other = store.clone();
+        let task = tokio::spawn(async move {
+            let mut txn = other.begin_txn().await.unwrap();
+            txn.put(b"count", &[0]).await.unwrap();
+            txn.commit().await.unwrap();
+        });
+        tokio::task::yield_now().await;
+        assert!(!task.is_finished());
+        drop(txn);
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_optimistic() {
+        let mut store = MemoryStore::default();
+        let mut txn = store.begin_txn().await.unwrap();
+        txn.put(b"key", b"value").await.unwrap();
+        txn.put(b"key2", b"v2").await.unwrap();
+        txn.commit().await.unwrap();
+
+        // changed reads fail the commit
+        let mut txn1 = store.begin_optimistic_txn().await.unwrap();
+        let mut txn2 = store.begin_optimistic_txn().await.unwrap();
+        assert_eq!(
+            txn1.get_for_update(b"key").await.unwrap().unwrap(),
+            b"value"
+        );
+        assert_eq!(
+            txn2.get_for_update(b"key").await.unwrap().unwrap(),
+            b"value"
+        );
+        txn1.put(b"key", b"v1").await.unwrap();
+        txn2.put(b"key", b"v2").await.unwrap();
+        txn1.commit().await.unwrap();
+        assert!(matches!(
+            txn2.commit().await,
+            Err(Error::TransactionConflict)
+        ));
+        assert_eq!(txn2.get(b"key").await.unwrap().unwrap(), b"v1");
+
+        // so do changed scans
+        let mut txn1 = store.begin_optimistic_txn().await.unwrap();
+        let mut txn2 = store.begin_txn().await.unwrap();
+        assert_eq!(
+            txn1.scan_keys(b"key", b"key4", 10).await.unwrap().count(),
+            2
+        );
+        txn2.put(b"key3", b"v3").await.unwrap();
+        txn2.commit().await.unwrap();
+        txn1.put(b"count", b"2").await.unwrap();
+        assert!(matches!(
+            txn1.commit().await,
+            Err(Error::TransactionConflict)
+        ));
+
+        // while unrelated changes do not
+        let mut txn1 = store.begin_optimistic_txn().await.unwrap();
+        let mut txn2 = store.begin_txn().await.unwrap();
+        assert_eq!(txn1.get(b"key").await.unwrap().unwrap(), b"v1");
+        txn2.put(b"key2", b"changed").await.unwrap();
+        txn2.commit().await.unwrap();
+        txn1.put(b"key", b"v3").await.unwrap();
+        txn1.commit().await.unwrap();
+        assert_eq!(txn1.get(b"key").await.unwrap().unwrap(), b"v3");
+    }
+
+    #[tokio::test]
+    async fn test_run_in_txn() {
+        let mut store = MemoryStore::default();
+        let policy = RetryPolicy {
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(2),
+        };
+
+        // conflicting attempts are retried
+        let mut attempts = 0;
+        let other = store.clone();
+        let count = store
+            .run_in_txn_with(policy, |mut txn| {
+                attempts += 1;
+                let interfere = attempts == 1;
+                let mut other = other.clone();
+                async move {
+                    let count = txn.get(b"count").await.unwrap().map_or(0, |v| v[0]);
+                    if interfere {
+                        let mut txn = other.begin_txn().await.unwrap();
+                        txn.put(b"count", &[10]).await.unwrap();
+                        txn.commit().await.unwrap();
+                    }
+                    let result = txn.put(b"count", &[count + 1]).await.map(|()| count + 1);
+                    (txn, result)
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(attempts, 2);
+        assert_eq!(count, 11);
+
+        // until retries run out
+        let mut attempts = 0;
+        let result = store
+            .run_in_txn_with(policy, |mut txn| {
+                attempts += 1;
+                let value = [attempts];
+                let mut other = other.clone();
+                async move {
+                    txn.get(b"count").await.unwrap();
+                    let mut other = other.begin_txn().await.unwrap();
+                    other.put(b"count", &value).await.unwrap();
+                    other.commit().await.unwrap();
+                    let result = txn.put(b"count", &[1]).await;
+                    (txn, result)
+                }
+            })
+            .await;
+        assert!(matches!(result, Err(Error::TransactionConflict)));
+        assert_eq!(attempts, 3);
+
+        // and other errors are returned as is, with writes rolled back
+        let result: Result<(), _> = store
+            .run_in_txn(|mut txn| async move {
+                txn.put(b"count", &[100]).await.unwrap();
+                (txn, Err(Error::NotImplemented))
+            })
+            .await;
+        assert!(matches!(result, Err(Error::NotImplemented)));
+        let txn = store.begin_txn().await.unwrap();
+        assert_eq!(txn.get(b"count").await.unwrap().unwrap(), [3]);
+    }
 }
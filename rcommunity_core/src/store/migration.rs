@@ -0,0 +1,323 @@
+//! Versioning of the store format, and migration of stores written in older
+//! versions.
+//!
+//! The format version of a store is kept in a metadata key. Stores written
+//! before the version was recorded are of version 1. Stores should be checked
+//! by [`check_version`] when opened, which refuses stores written in any other
+//! version. Older stores are upgraded by a [`Migrator`], one version at a time,
+//! rewriting keys in batches of bounded size, each in its own transaction.
+//!
+//! No migration from version 1 ships with this library: keys of version 1 do
+//! not delimit strings, so they cannot be decoded without knowing their types.
+//! Such stores must be upgraded by a [`Migration`] written for the types they
+//! hold.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+use super::{
+    format::{from_value, to_key, to_value},
+    Store, Transaction,
+};
+
+/// Current version of the store format.
+///
+/// Version 2 escapes and terminates strings, encodes integers by length,
+/// names types by tag, and prepends a codec tag byte to values.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// Exclusive upper bound of all keys, which start with ASCII type names.
+const KEYS_END: &[u8] = &[0xFF];
+
+#[derive(Serialize)]
+struct FormatVersionKey {}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "FormatVersionValue")]
+struct FormatVersionValue {
+    version: u32,
+    /// Key to resume an unfinished migration from.
+    cursor: Option<Vec<u8>>,
+}
+
+fn version_key() -> Result<Vec<u8>> {
+    Ok(to_key(&FormatVersionKey {})?)
+}
+
+/// Read format version of store, along with the migration cursor if any, or
+/// record the current version for an empty store.
+async fn read_version(txn: &mut impl Transaction) -> Result<FormatVersionValue> {
+    let key = version_key()?;
+    if let Some(value) = txn.get_for_update(&key).await? {
+        return from_value(&value).map_err(|source| Error::CorruptData { key, source });
+    }
+    let is_empty = txn.scan_keys(&[], KEYS_END, 1).await?.next().is_none();
+    let state = FormatVersionValue {
+        version: if is_empty { FORMAT_VERSION } else { 1 },
+        cursor: None,
+    };
+    if is_empty {
+        txn.put(&key, &to_value(&state)?).await?;
+    }
+    Ok(state)
+}
+
+/// Read format version of store, refusing newer versions.
+async fn stored_version(store: &mut impl Store) -> Result<u32> {
+    let mut txn = store.begin_txn().await?;
+    let state = read_version(&mut txn).await?;
+    txn.commit().await?;
+    if state.version > FORMAT_VERSION {
+        return Err(Error::UnsupportedVersion {
+            found: state.version,
+            supported: FORMAT_VERSION,
+        });
+    }
+    Ok(state.version)
+}
+
+/// Check that store is written in the current format version, returning it.
+/// Empty stores are marked with the current version.
+///
+/// # Errors
+/// Will return `Err` if store is written in a newer format version than this
+/// library supports, or in an older one and not yet migrated, or on store
+/// errors.
+pub async fn check_version(store: &mut impl Store) -> Result<u32> {
+    let version = stored_version(store).await?;
+    if version < FORMAT_VERSION {
+        return Err(Error::MigrationRequired {
+            found: version,
+            current: FORMAT_VERSION,
+        });
+    }
+    Ok(version)
+}
+
+/// Migration of keys from one format version to the next.
+pub trait Migration: Send + Sync {
+    /// Version migrated from, to `source_version() + 1`.
+    fn source_version(&self) -> u32;
+
+    /// Key range to migrate, as `(start, end)` with `end` exclusive. Defaults
+    /// to all keys.
+    fn range(&self) -> (Vec<u8>, Vec<u8>) {
+        (Vec::new(), KEYS_END.to_vec())
+    }
+
+    /// Rewrite a key-value pair to the new layout, or return `None` to keep it
+    /// as is.
+    ///
+    /// Rewritten pairs may be visited again later in the same migration, so
+    /// pairs already in the new layout must be kept.
+    ///
+    /// # Errors
+    /// Will return `Err` if pair cannot be migrated, which aborts the
+    /// migration. The migration resumes from the failed batch next time.
+    fn migrate(&self, key: &[u8], value: &[u8]) -> Result<Option<(Vec<u8>, Vec<u8>)>>;
+}
+
+/// Upgrades stores to the current format version by running migrations.
+pub struct Migrator {
+    migrations: Vec<Box<dyn Migration>>,
+    batch_size: usize,
+}
+
+impl Default for Migrator {
+    fn default() -> Self {
+        Self {
+            migrations: Vec::new(),
+            batch_size: 1000,
+        }
+    }
+}
+
+impl Migrator {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum number of pairs migrated in one transaction.
+    #[must_use]
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Register a migration.
+    #[must_use]
+    pub fn register(mut self, migration: impl Migration + 'static) -> Self {
+        self.migrations.push(Box::new(migration));
+        self
+    }
+
+    /// Migrate store to the current format version, returning the version
+    /// migrated from.
+    ///
+    /// # Errors
+    /// Will return `Err` if store is written in a newer format version, a
+    /// migration is missing or fails, or on store errors.
+    pub async fn run(&self, store: &mut impl Store) -> Result<u32> {
+        let from = stored_version(store).await?;
+        while self.run_batch(store).await? {}
+        Ok(from)
+    }
+
+    /// Migrate one batch, returning whether there are more to migrate.
+    async fn run_batch(&self, store: &mut impl Store) -> Result<bool> {
+        let mut txn = store.begin_txn().await?;
+        let mut state = read_version(&mut txn).await?;
+        if state.version >= FORMAT_VERSION {
+            txn.commit().await?;
+            return Ok(false);
+        }
+        let migration = self
+            .migrations
+            .iter()
+            .find(|m| m.source_version() == state.version)
+            .ok_or(Error::MissingMigration {
+                version: state.version,
+            })?;
+
+        let version_key = version_key()?;
+        let (start, end) = migration.range();
+        let start = state.cursor.take().unwrap_or(start);
+        let pairs: Vec<_> = txn.scan(&start, &end, self.batch_size).await?.collect();
+        let done = pairs.len() < self.batch_size;
+        let mut cursor = start;
+        for (key, value) in pairs {
+            if key != version_key {
+                if let Some((new_key, new_value)) = migration.migrate(&key, &value)? {
+                    if new_key != key {
+                        txn.delete(&key).await?;
+                    }
+                    txn.put(&new_key, &new_value).await?;
+                }
+            }
+            // smallest key after the current one
+            cursor = key;
+            cursor.push(0);
+        }
+
+        if done {
+            state.version += 1;
+        } else {
+            state.cursor = Some(cursor);
+        }
+        txn.put(&version_key, &to_value(&state)?).await?;
+        txn.commit().await?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        error::Error,
+        store::{
+            format::{to_key, to_value},
+            memory::MemoryStore,
+            Store, Transaction,
+        },
+    };
+
+    use super::{
+        check_version, version_key, FormatVersionValue, Migration, Migrator, FORMAT_VERSION,
+    };
+
+    /// Upgrades keys of a legacy `Name:value` layout.
+    struct Legacy;
+
+    impl Migration for Legacy {
+        fn source_version(&self) -> u32 {
+            1
+        }
+
+        fn migrate(
+            &self,
+            key: &[u8],
+            value: &[u8],
+        ) -> crate::error::Result<Option<(Vec<u8>, Vec<u8>)>> {
+            let key = std::str::from_utf8(key).unwrap();
+            Ok(key
+                .strip_prefix("Legacy:")
+                .map(|rest| (to_key(&("Current", rest)).unwrap(), value.to_vec())))
+        }
+    }
+
+    async fn set_version(store: &mut MemoryStore, version: u32) {
+        let mut txn = store.begin_txn().await.unwrap();
+        let value = FormatVersionValue {
+            version,
+            cursor: None,
+        };
+        txn.put(&version_key().unwrap(), &to_value(&value).unwrap())
+            .await
+            .unwrap();
+        txn.commit().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_check_version() {
+        let mut store = MemoryStore::default();
+        assert_eq!(check_version(&mut store).await.unwrap(), FORMAT_VERSION);
+        // version is recorded for new stores
+        let txn = store.begin_txn().await.unwrap();
+        assert!(txn.get(&version_key().unwrap()).await.unwrap().is_some());
+        drop(txn);
+
+        set_version(&mut store, FORMAT_VERSION + 1).await;
+        assert!(matches!(
+            check_version(&mut store).await,
+            Err(Error::UnsupportedVersion { found, .. }) if found == FORMAT_VERSION + 1
+        ));
+        assert!(matches!(
+            Migrator::new().run(&mut store).await,
+            Err(Error::UnsupportedVersion { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_migration() {
+        let mut store = MemoryStore::default();
+        let mut txn = store.begin_txn().await.unwrap();
+        for i in 0..10 {
+            let key = format!("Legacy:{i}");
+            txn.put(key.as_bytes(), b"v").await.unwrap();
+        }
+        txn.put(b"Other", b"o").await.unwrap();
+        txn.commit().await.unwrap();
+
+        // stores without version are of version 1, refused until migrated
+        assert!(matches!(
+            check_version(&mut store).await,
+            Err(Error::MigrationRequired {
+                found: 1,
+                current: FORMAT_VERSION
+            })
+        ));
+        assert!(matches!(
+            Migrator::new().run(&mut store).await,
+            Err(Error::MissingMigration { version: 1 })
+        ));
+
+        let migrator = Migrator::new().batch_size(3).register(Legacy);
+        assert_eq!(migrator.run(&mut store).await.unwrap(), 1);
+        assert_eq!(check_version(&mut store).await.unwrap(), FORMAT_VERSION);
+
+        let txn = store.begin_txn().await.unwrap();
+        for i in 0..10 {
+            let key = to_key(&("Current", i.to_string())).unwrap();
+            assert_eq!(txn.get(&key).await.unwrap().unwrap(), b"v");
+            let key = format!("Legacy:{i}");
+            assert!(txn.get(key.as_bytes()).await.unwrap().is_none());
+        }
+        assert_eq!(txn.get(b"Other").await.unwrap().unwrap(), b"o");
+        drop(txn);
+
+        // nothing to do once up to date
+        assert_eq!(migrator.run(&mut store).await.unwrap(), FORMAT_VERSION);
+    }
+}
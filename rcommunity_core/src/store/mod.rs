@@ -2,18 +2,145 @@
 
 pub mod format;
 pub mod memory;
+pub mod migration;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+#[cfg(feature = "redb")]
+pub mod redb;
+#[cfg(feature = "redis")]
+pub mod redis;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+use std::{future::Future, time::Duration};
 
 use async_trait::async_trait;
 
-use crate::error::Result;
+use crate::error::{Error, Result};
+
+use format::Codec;
 
 /// Abstraction for the backing storage layer. Represents a transactional API.
 #[async_trait]
-pub trait Store {
+pub trait Store: Send {
     /// The corresponding [`Transaction`] type.
     type Transaction: Transaction;
     /// Begins a transaction for further operations. See [`Transaction`].
     async fn begin_txn(&mut self) -> Result<Self::Transaction>;
+    /// Begins an optimistic transaction, which takes no lock but fails to
+    /// commit with [`Error::TransactionConflict`] if any of its reads is
+    /// changed by others. The default suits stores detecting conflicts, or
+    /// serializing transactions already.
+    async fn begin_optimistic_txn(&mut self) -> Result<Self::Transaction> {
+        self.begin_txn().await
+    }
+    /// Run `f` in an optimistic transaction, committing it if `f` succeeds and
+    /// rolling it back otherwise. Retried on conflicts with the default
+    /// [`RetryPolicy`].
+    ///
+    /// `f` takes the transaction and gives it back along with its result.
+    async fn run_in_txn<F, Fut, R>(&mut self, f: F) -> Result<R>
+    where
+        F: FnMut(Self::Transaction) -> Fut + Send,
+        Fut: Future<Output = (Self::Transaction, Result<R>)> + Send,
+        R: Send,
+    {
+        self.run_in_txn_with(RetryPolicy::default(), f).await
+    }
+    /// Same as [`Store::run_in_txn`], retried on conflicts with `policy`.
+    async fn run_in_txn_with<F, Fut, R>(&mut self, policy: RetryPolicy, mut f: F) -> Result<R>
+    where
+        F: FnMut(Self::Transaction) -> Fut + Send,
+        Fut: Future<Output = (Self::Transaction, Result<R>)> + Send,
+        R: Send,
+    {
+        let mut backoff = policy.initial_backoff;
+        let mut retries = 0;
+        loop {
+            let txn = self.begin_optimistic_txn().await?;
+            let (mut txn, result) = f(txn).await;
+            let result = match result {
+                Ok(r) => txn.commit().await.map(|()| r),
+                Err(e) => {
+                    if let Err(err) = txn.rollback().await {
+                        tracing::warn!("failed to rollback transaction: {err}");
+                    }
+                    Err(e)
+                }
+            };
+            match result {
+                Err(Error::TransactionConflict) if retries < policy.max_retries => {
+                    retries += 1;
+                    futures_timer::Delay::new(backoff).await;
+                    backoff = (backoff * 2).min(policy.max_backoff);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Direction of a paginated scan, see [`Transaction::scan_page`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Direction {
+    /// In ascending key order.
+    #[default]
+    Forward,
+    /// In descending key order.
+    Backward,
+}
+
+/// Narrow `start..end` to keys after `cursor` in `direction`.
+fn page_range(
+    start: &[u8],
+    end: &[u8],
+    cursor: Option<&[u8]>,
+    direction: Direction,
+) -> (Vec<u8>, Vec<u8>) {
+    let (mut start, mut end) = (start.to_vec(), end.to_vec());
+    match (cursor, direction) {
+        (None, _) => {}
+        (Some(cursor), Direction::Forward) => {
+            // the least key greater than `cursor`
+            let mut next = cursor.to_vec();
+            next.push(0);
+            start = start.max(next);
+        }
+        (Some(cursor), Direction::Backward) => end = end.min(cursor.to_vec()),
+    }
+    if start > end {
+        end.clone_from(&start);
+    }
+    (start, end)
+}
+
+/// Run blocking database calls in `f` on the blocking thread pool.
+#[cfg(any(feature = "redb", feature = "sqlite"))]
+async fn blocking<R: Send + 'static>(f: impl FnOnce() -> Result<R> + Send + 'static) -> Result<R> {
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => result,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+/// Retry policy of [`Store::run_in_txn_with`]. Backoff between retries starts
+/// from `initial_backoff`, and doubles each time up to `max_backoff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Times to retry before giving up.
+    pub max_retries: usize,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
 }
 
 /// Abstraction of all supported operations in a transaction corresponding to a
@@ -35,11 +162,72 @@ pub trait Transaction: Send + Sync {
     async fn scan(&self, start: &[u8], end: &[u8], limit: usize) -> Result<Self::PairIterator>;
     /// Scan for all keys within a key range from store.
     async fn scan_keys(&self, start: &[u8], end: &[u8], limit: usize) -> Result<Self::KeyIterator>;
+    /// Scan for key-value pairs within a key range from store, in descending
+    /// key order. Implementation of this method is not required, in which case
+    /// [`Error::NotImplemented`] is returned.
+    async fn scan_rev(
+        &self,
+        _start: &[u8],
+        _end: &[u8],
+        _limit: usize,
+    ) -> Result<Self::PairIterator> {
+        Err(Error::NotImplemented)
+    }
+    /// Scan for all keys within a key range from store, in descending key
+    /// order. Implementation of this method is not required, in which case
+    /// [`Error::NotImplemented`] is returned.
+    async fn scan_keys_rev(
+        &self,
+        _start: &[u8],
+        _end: &[u8],
+        _limit: usize,
+    ) -> Result<Self::KeyIterator> {
+        Err(Error::NotImplemented)
+    }
+    /// Scan for a page of key-value pairs within a key range from store,
+    /// continuing from `cursor` exclusively in `direction`. The key of the last
+    /// pair is the cursor of the next page.
+    async fn scan_page(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        cursor: Option<&[u8]>,
+        direction: Direction,
+        limit: usize,
+    ) -> Result<Self::PairIterator> {
+        let (start, end) = page_range(start, end, cursor, direction);
+        match direction {
+            Direction::Forward => self.scan(&start, &end, limit).await,
+            Direction::Backward => self.scan_rev(&start, &end, limit).await,
+        }
+    }
+    /// Scan for a page of keys within a key range from store, continuing from
+    /// `cursor` exclusively in `direction`. The last key is the cursor of the
+    /// next page.
+    async fn scan_keys_page(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        cursor: Option<&[u8]>,
+        direction: Direction,
+        limit: usize,
+    ) -> Result<Self::KeyIterator> {
+        let (start, end) = page_range(start, end, cursor, direction);
+        match direction {
+            Direction::Forward => self.scan_keys(&start, &end, limit).await,
+            Direction::Backward => self.scan_keys_rev(&start, &end, limit).await,
+        }
+    }
+    /// Codec of values written in this transaction. Values written by any
+    /// enabled codec can be read back.
+    fn codec(&self) -> Codec {
+        Codec::default()
+    }
     /// Commit this transaction.
     async fn commit(&mut self) -> Result<()>;
     /// Rollback this transaction. Implementation of this method is not
-    /// required.
+    /// required, in which case [`Error::NotImplemented`] is returned.
     async fn rollback(&mut self) -> Result<()> {
-        unimplemented!("this transaction does not support rollback");
+        Err(Error::NotImplemented)
     }
 }
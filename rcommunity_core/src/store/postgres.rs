@@ -0,0 +1,419 @@
+//! A Postgres-backed [`Store`] implementation, enabled by feature
+//! `postgres`.
+//!
+//! # Example Usage
+//! ```rust,no_run
+//! use rcommunity_core::store::postgres::PgStore;
+//! use rcommunity_core::store::{Store, Transaction};
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! let mut store = PgStore::open("host=localhost user=postgres").await.unwrap();
+//! let mut txn = store.begin_txn().await.unwrap();
+//! txn.put(b"key", b"value").await.unwrap();
+//! txn.commit().await.unwrap();
+//! # }
+//! ```
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use ::tokio_postgres::{Client, Config, NoTls};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+
+use crate::error::{Error, Result};
+
+use super::{format::Codec, migration::check_version, Store, Transaction};
+
+/// Times to replay a transaction aborted by a serialization failure.
+const MAX_RETRIES: usize = 3;
+
+/// Implementation of a Postgres-backed [`Store`].
+///
+/// All key-value pairs are kept in a single table
+/// `rcommunity (key BYTEA PRIMARY KEY, value BYTEA)`, and range scans follow
+/// the byte order of `bytea` keys. Each transaction runs on its own connection
+/// at `SERIALIZABLE` isolation level, and [`Transaction::get_for_update`] locks
+/// the row with `SELECT ... FOR UPDATE`.
+///
+/// Operations in a transaction are logged along with their results. When a
+/// transaction is aborted by a serialization failure, it is replayed in a new
+/// one, and continues if all reads return the same results as before.
+/// Otherwise, [`Error::TransactionConflict`] is returned.
+///
+/// Connections are driven on the current Tokio runtime.
+#[derive(Debug, Clone)]
+pub struct PgStore {
+    config: Config,
+    codec: Codec,
+}
+
+impl PgStore {
+    /// Open a store on the Postgres server configured by `config`, in
+    /// [`tokio_postgres::Config`] format, creating the table if not existing.
+    ///
+    /// # Errors
+    /// Will return `Err` if server is unreachable, or store is written in
+    /// another format version than the current one, see [`check_version`].
+    pub async fn open(config: &str) -> Result<Self> {
+        let mut store = Self::open_unchecked(config).await?;
+        check_version(&mut store).await?;
+        Ok(store)
+    }
+
+    /// Open a store like [`PgStore::open`], without checking its format
+    /// version, so that it can be upgraded by a [`Migrator`].
+    ///
+    /// # Errors
+    /// Will return `Err` if server is unreachable.
+    ///
+    /// [`Migrator`]: super::migration::Migrator
+    pub async fn open_unchecked(config: &str) -> Result<Self> {
+        let store = Self {
+            config: config.parse()?,
+            codec: Codec::default(),
+        };
+        store
+            .connect()
+            .await?
+            .batch_execute(
+                "CREATE TABLE IF NOT EXISTS rcommunity (key BYTEA PRIMARY KEY, value BYTEA NOT NULL)",
+            )
+            .await?;
+        Ok(store)
+    }
+
+    /// Write values with `codec`.
+    #[must_use]
+    pub fn with_codec(self, codec: Codec) -> Self {
+        Self { codec, ..self }
+    }
+
+    async fn connect(&self) -> Result<Client> {
+        let (client, connection) = self.config.connect(NoTls).await?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                tracing::error!("PostgreSQL connection error: {}", e);
+            }
+        });
+        Ok(client)
+    }
+}
+
+#[async_trait]
+impl Store for PgStore {
+    type Transaction = PgTransaction;
+    async fn begin_txn(&mut self) -> Result<PgTransaction> {
+        Ok(PgTransaction {
+            client: self.connect().await?,
+            active: AtomicBool::new(false),
+            log: Mutex::new(Vec::new()),
+            codec: self.codec,
+        })
+    }
+}
+
+/// An operation in a transaction, along with its result if any.
+#[derive(Clone, PartialEq, Eq)]
+enum Op {
+    Get {
+        key: Vec<u8>,
+        for_update: bool,
+        value: Option<Vec<u8>>,
+    },
+    Put {
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Delete {
+        key: Vec<u8>,
+    },
+    Scan {
+        start: Vec<u8>,
+        end: Vec<u8>,
+        limit: usize,
+        reverse: bool,
+        pairs: Vec<(Vec<u8>, Vec<u8>)>,
+    },
+}
+
+/// Serialization failures and deadlocks are reported as
+/// [`Error::TransactionConflict`].
+fn is_serialization_failure(e: &Error) -> bool {
+    matches!(e, Error::TransactionConflict)
+}
+
+/// Transaction type for [`PgStore`].
+///
+/// A SQL transaction begins on first use, and uncommitted writes are rolled
+/// back when dropped.
+pub struct PgTransaction {
+    client: Client,
+    /// Whether a SQL transaction has begun.
+    active: AtomicBool,
+    log: Mutex<Vec<Op>>,
+    codec: Codec,
+}
+
+impl PgTransaction {
+    async fn begin(&self) -> Result<()> {
+        if !self.active.swap(true, Ordering::SeqCst) {
+            self.client
+                .batch_execute("BEGIN ISOLATION LEVEL SERIALIZABLE")
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn end(&self, statement: &str) -> Result<()> {
+        self.log.lock().clear();
+        if self.active.swap(false, Ordering::SeqCst) {
+            self.client.batch_execute(statement).await?;
+        }
+        Ok(())
+    }
+
+    /// Execute an operation, returning it with the current result.
+    async fn exec(&self, op: &Op) -> Result<Op> {
+        Ok(match op {
+            Op::Get {
+                key, for_update, ..
+            } => {
+                let sql = if *for_update {
+                    "SELECT value FROM rcommunity WHERE key = $1 FOR UPDATE"
+                } else {
+                    "SELECT value FROM rcommunity WHERE key = $1"
+                };
+                let row = self.client.query_opt(sql, &[key]).await?;
+                Op::Get {
+                    key: key.clone(),
+                    for_update: *for_update,
+                    value: row.map(|row| row.get(0)),
+                }
+            }
+            Op::Put { key, value } => {
+                self.client
+                    .execute(
+                        "INSERT INTO rcommunity (key, value) VALUES ($1, $2) \
+                        ON CONFLICT (key) DO UPDATE SET value = EXCLUDED.value",
+                        &[key, value],
+                    )
+                    .await?;
+                op.clone()
+            }
+            Op::Delete { key } => {
+                self.client
+                    .execute("DELETE FROM rcommunity WHERE key = $1", &[key])
+                    .await?;
+                op.clone()
+            }
+            Op::Scan {
+                start,
+                end,
+                limit,
+                reverse,
+                ..
+            } => {
+                let sql = if *reverse {
+                    "SELECT key, value FROM rcommunity \
+                    WHERE key >= $1 AND key < $2 ORDER BY key DESC LIMIT $3"
+                } else {
+                    "SELECT key, value FROM rcommunity \
+                    WHERE key >= $1 AND key < $2 ORDER BY key LIMIT $3"
+                };
+                // no limit if `NULL`
+                let count = i64::try_from(*limit).ok();
+                let rows = self.client.query(sql, &[start, end, &count]).await?;
+                Op::Scan {
+                    start: start.clone(),
+                    end: end.clone(),
+                    limit: *limit,
+                    reverse: *reverse,
+                    pairs: rows.iter().map(|row| (row.get(0), row.get(1))).collect(),
+                }
+            }
+        })
+    }
+
+    /// Replay logged operations in a new transaction.
+    async fn replay(&self) -> Result<()> {
+        let log = self.log.lock().clone();
+        for _ in 0..MAX_RETRIES {
+            self.client
+                .batch_execute("ROLLBACK; BEGIN ISOLATION LEVEL SERIALIZABLE")
+                .await?;
+            match self.replay_once(&log).await {
+                Ok(true) => return Ok(()),
+                Ok(false) => break,
+                Err(e) if is_serialization_failure(&e) => {}
+                Err(e) => {
+                    self.end("ROLLBACK").await?;
+                    return Err(e);
+                }
+            }
+        }
+        self.end("ROLLBACK").await?;
+        Err(Error::TransactionConflict)
+    }
+
+    /// Replay logged operations, returning whether all results are the same.
+    async fn replay_once(&self, log: &[Op]) -> Result<bool> {
+        for op in log {
+            if &self.exec(op).await? != op {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Execute and log an operation, replaying the transaction on
+    /// serialization failures.
+    async fn run(&self, op: Op) -> Result<Op> {
+        self.begin().await?;
+        let mut retries = 0;
+        loop {
+            match self.exec(&op).await {
+                Ok(result) => {
+                    self.log.lock().push(result.clone());
+                    return Ok(result);
+                }
+                Err(e) if is_serialization_failure(&e) && retries < MAX_RETRIES => {
+                    retries += 1;
+                    self.replay().await?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn scan_pairs(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        limit: usize,
+        reverse: bool,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let op = Op::Scan {
+            start: start.to_vec(),
+            end: end.to_vec(),
+            limit,
+            reverse,
+            pairs: Vec::new(),
+        };
+        match self.run(op).await? {
+            Op::Scan { pairs, .. } => Ok(pairs),
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[async_trait]
+impl Transaction for PgTransaction {
+    type KeyIterator = std::vec::IntoIter<Vec<u8>>;
+    type PairIterator = std::vec::IntoIter<(Vec<u8>, Vec<u8>)>;
+
+    async fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let op = Op::Get {
+            key: key.to_vec(),
+            for_update: false,
+            value: None,
+        };
+        match self.run(op).await? {
+            Op::Get { value, .. } => Ok(value),
+            _ => unreachable!(),
+        }
+    }
+
+    async fn get_for_update(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let op = Op::Get {
+            key: key.to_vec(),
+            for_update: true,
+            value: None,
+        };
+        match self.run(op).await? {
+            Op::Get { value, .. } => Ok(value),
+            _ => unreachable!(),
+        }
+    }
+
+    async fn put(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.run(Op::Put {
+            key: key.to_vec(),
+            value: value.to_vec(),
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn delete(&mut self, key: &[u8]) -> Result<()> {
+        self.run(Op::Delete { key: key.to_vec() }).await?;
+        Ok(())
+    }
+
+    async fn scan(&self, start: &[u8], end: &[u8], limit: usize) -> Result<Self::PairIterator> {
+        Ok(self.scan_pairs(start, end, limit, false).await?.into_iter())
+    }
+
+    async fn scan_keys(&self, start: &[u8], end: &[u8], limit: usize) -> Result<Self::KeyIterator> {
+        let keys: Vec<_> = self
+            .scan_pairs(start, end, limit, false)
+            .await?
+            .into_iter()
+            .map(|(k, _)| k)
+            .collect();
+        Ok(keys.into_iter())
+    }
+
+    async fn scan_rev(&self, start: &[u8], end: &[u8], limit: usize) -> Result<Self::PairIterator> {
+        Ok(self.scan_pairs(start, end, limit, true).await?.into_iter())
+    }
+
+    async fn scan_keys_rev(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        limit: usize,
+    ) -> Result<Self::KeyIterator> {
+        let keys: Vec<_> = self
+            .scan_pairs(start, end, limit, true)
+            .await?
+            .into_iter()
+            .map(|(k, _)| k)
+            .collect();
+        Ok(keys.into_iter())
+    }
+
+    fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    async fn commit(&mut self) -> Result<()> {
+        if !self.active.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        let mut retries = 0;
+        loop {
+            match self.client.batch_execute("COMMIT").await {
+                Ok(()) => break,
+                Err(e) => {
+                    let e = Error::from(e);
+                    if !is_serialization_failure(&e) || retries == MAX_RETRIES {
+                        // a failed commit ends the transaction already
+                        self.active.store(false, Ordering::SeqCst);
+                        self.log.lock().clear();
+                        return Err(e);
+                    }
+                    retries += 1;
+                    self.replay().await?;
+                }
+            }
+        }
+        self.active.store(false, Ordering::SeqCst);
+        self.log.lock().clear();
+        Ok(())
+    }
+
+    async fn rollback(&mut self) -> Result<()> {
+        self.end("ROLLBACK").await
+    }
+}
@@ -0,0 +1,573 @@
+//! An embedded persistent [`Store`] implementation backed by [redb], enabled
+//! by feature `redb`.
+//!
+//! # Example Usage
+//! ```rust
+//! use rcommunity_core::store::redb::RedbStore;
+//! use rcommunity_core::store::{Store, Transaction};
+//!
+//! tokio_test::block_on(async {
+//!     let mut store = RedbStore::in_memory().await.unwrap();
+//!     let mut txn = store.begin_txn().await.unwrap();
+//!     txn.put(b"key", b"value").await.unwrap();
+//!     txn.commit().await.unwrap();
+//!     let txn = store.begin_txn().await.unwrap();
+//!     assert_eq!(
+//!         txn.get(b"key").await.unwrap().unwrap(),
+//!         b"value",
+//!     );
+//! })
+//! ```
+//!
+//! [redb]: https://docs.rs/redb
+
+use std::{path::Path, sync::Arc};
+
+use ::redb::{
+    backends::InMemoryBackend, Database, ReadTransaction, ReadableTable, TableDefinition,
+    WriteTransaction,
+};
+use async_lock::{Mutex as AsyncMutex, MutexGuardArc};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+
+use crate::error::{Error, Result};
+
+use super::{blocking, format::Codec, migration::check_version, Store, Transaction};
+
+const TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("rcommunity");
+
+/// Implementation of a [`Store`] persisted in a redb database file.
+///
+/// A [`RedbTransaction`] reads from a redb read transaction, a snapshot that
+/// never blocks, until its first [`Transaction::get_for_update`] or write.
+/// From then on it holds a redb write transaction, of which at most one is
+/// open at a time: a transaction waits for the running one to commit or roll
+/// back by taking the write lock, an async-aware mutex, so the runtime is
+/// never blocked. Reads from the snapshot are checked again in the write
+/// transaction, which fails with [`Error::TransactionConflict`] if any of them
+/// is changed by others meanwhile. Commits run on the blocking thread pool of
+/// tokio, so the store must be used within a tokio runtime. Uncommitted writes
+/// are discarded when dropped.
+///
+/// [`Error::TransactionConflict`]: crate::error::Error::TransactionConflict
+#[derive(Debug, Clone)]
+pub struct RedbStore {
+    db: Arc<Database>,
+    write_lock: Arc<AsyncMutex<()>>,
+    codec: Codec,
+}
+
+impl RedbStore {
+    /// Open a store at `path`, creating the database file if not existing.
+    ///
+    /// # Errors
+    /// Will return `Err` if database cannot be opened, or store is written in
+    /// another format version than the current one, see [`check_version`].
+    pub async fn open(path: impl AsRef<Path> + Send) -> Result<Self> {
+        let mut store = Self::open_unchecked(path)?;
+        check_version(&mut store).await?;
+        Ok(store)
+    }
+
+    /// Open a store at `path` like [`RedbStore::open`], without checking its
+    /// format version, so that it can be upgraded by a [`Migrator`].
+    ///
+    /// # Errors
+    /// Will return `Err` if database cannot be opened.
+    ///
+    /// [`Migrator`]: super::migration::Migrator
+    pub fn open_unchecked(path: impl AsRef<Path>) -> Result<Self> {
+        let db = Database::create(path).map_err(::redb::Error::from)?;
+        Self::new(db)
+    }
+
+    /// Open a store kept in memory.
+    ///
+    /// # Errors
+    /// Will return `Err` if database cannot be created.
+    pub async fn in_memory() -> Result<Self> {
+        let db = Database::builder()
+            .create_with_backend(InMemoryBackend::new())
+            .map_err(::redb::Error::from)?;
+        let mut store = Self::new(db)?;
+        check_version(&mut store).await?;
+        Ok(store)
+    }
+
+    fn new(db: Database) -> Result<Self> {
+        // create the table up front, so that read transactions can open it
+        let txn = db.begin_write().map_err(::redb::Error::from)?;
+        txn.open_table(TABLE).map_err(::redb::Error::from)?;
+        txn.commit().map_err(::redb::Error::from)?;
+        Ok(Self {
+            db: Arc::new(db),
+            write_lock: Arc::new(AsyncMutex::new(())),
+            codec: Codec::default(),
+        })
+    }
+
+    /// Write values with `codec`.
+    #[must_use]
+    pub fn with_codec(self, codec: Codec) -> Self {
+        Self { codec, ..self }
+    }
+}
+
+#[async_trait]
+impl Store for RedbStore {
+    type Transaction = RedbTransaction;
+    async fn begin_txn(&mut self) -> Result<RedbTransaction> {
+        Ok(RedbTransaction {
+            db: self.db.clone(),
+            write_lock: self.write_lock.clone(),
+            read_txn: Mutex::new(None),
+            reads: Mutex::default(),
+            write_txn: None,
+            guard: None,
+            codec: self.codec,
+        })
+    }
+}
+
+/// Transaction type for [`RedbStore`].
+pub struct RedbTransaction {
+    db: Arc<Database>,
+    write_lock: Arc<AsyncMutex<()>>,
+    /// Read snapshot, begun on first read before any write.
+    read_txn: Mutex<Option<ReadTransaction>>,
+    /// Reads from the snapshot, checked again once writing.
+    reads: Mutex<Vec<Read>>,
+    /// Write transaction, begun on first write. Declared before `guard` so
+    /// that it is dropped, and aborted, before the write lock is released.
+    write_txn: Option<WriteTransaction>,
+    /// Write lock, if held by this transaction.
+    guard: Option<MutexGuardArc<()>>,
+    codec: Codec,
+}
+
+/// A read from the snapshot, along with its result.
+enum Read {
+    Key(Vec<u8>, Option<Vec<u8>>),
+    Range {
+        start: Vec<u8>,
+        end: Vec<u8>,
+        limit: usize,
+        reverse: bool,
+        pairs: Vec<(Vec<u8>, Vec<u8>)>,
+    },
+}
+
+impl Read {
+    /// Whether this read returns the same result from `table`.
+    fn is_valid(&self, table: &impl ReadableTable<&'static [u8], &'static [u8]>) -> Result<bool> {
+        Ok(match self {
+            Read::Key(key, value) => get_in(table, key)? == *value,
+            Read::Range {
+                start,
+                end,
+                limit,
+                reverse,
+                pairs,
+            } => range_in(table, start, end, *limit, *reverse)? == *pairs,
+        })
+    }
+}
+
+fn get_in(
+    table: &impl ReadableTable<&'static [u8], &'static [u8]>,
+    key: &[u8],
+) -> Result<Option<Vec<u8>>> {
+    let value = table.get(key).map_err(::redb::Error::from)?;
+    Ok(value.map(|v| v.value().to_vec()))
+}
+
+/// Pairs within range in `table`, in descending key order if `reverse`.
+fn range_in(
+    table: &impl ReadableTable<&'static [u8], &'static [u8]>,
+    start: &[u8],
+    end: &[u8],
+    limit: usize,
+    reverse: bool,
+) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    let mut pairs = Vec::new();
+    if start >= end {
+        return Ok(pairs);
+    }
+    let range = table
+        .range::<&[u8]>(start..end)
+        .map_err(::redb::Error::from)?;
+    let entries: Box<dyn Iterator<Item = _>> = if reverse {
+        Box::new(range.rev())
+    } else {
+        Box::new(range)
+    };
+    for entry in entries.take(limit) {
+        let (key, value) = entry.map_err(::redb::Error::from)?;
+        pairs.push((key.value().to_vec(), value.value().to_vec()));
+    }
+    Ok(pairs)
+}
+
+/// Run `$body` with `$table` bound to the table, opened in the write
+/// transaction if begun, or in the read snapshot otherwise, and `$snapshot`
+/// telling which.
+macro_rules! with_table {
+    ($txn:expr, |$table:ident, $snapshot:ident| $body:expr) => {{
+        let txn = $txn;
+        if let Some(write_txn) = &txn.write_txn {
+            let $table = write_txn.open_table(TABLE).map_err(::redb::Error::from)?;
+            let $snapshot = false;
+            $body
+        } else {
+            let mut read_txn = txn.read_txn.lock();
+            if read_txn.is_none() {
+                *read_txn = Some(txn.db.begin_read().map_err(::redb::Error::from)?);
+            }
+            let $table = read_txn
+                .as_ref()
+                .unwrap()
+                .open_table(TABLE)
+                .map_err(::redb::Error::from)?;
+            let $snapshot = true;
+            $body
+        }
+    }};
+}
+
+impl RedbTransaction {
+    /// Begin the underlying write transaction, if not yet, waiting for the
+    /// running one to finish.
+    ///
+    /// # Errors
+    /// Will return [`Error::TransactionConflict`] if reads from the snapshot
+    /// are changed by others.
+    async fn write(&mut self) -> Result<&WriteTransaction> {
+        if self.write_txn.is_none() {
+            let guard = self.write_lock.lock_arc().await;
+            // no other write transaction is open, so this does not block
+            let txn = self.db.begin_write().map_err(::redb::Error::from)?;
+            self.read_txn.lock().take();
+            let reads = std::mem::take(self.reads.get_mut());
+            if !reads.is_empty() {
+                let table = txn.open_table(TABLE).map_err(::redb::Error::from)?;
+                for read in &reads {
+                    if !read.is_valid(&table)? {
+                        return Err(Error::TransactionConflict);
+                    }
+                }
+            }
+            self.guard = Some(guard);
+            self.write_txn = Some(txn);
+        }
+        Ok(self.write_txn.as_ref().unwrap())
+    }
+
+    /// End this transaction, releasing the write lock.
+    fn end(&mut self) {
+        self.read_txn.lock().take();
+        self.reads.get_mut().clear();
+        self.write_txn = None;
+        self.guard = None;
+    }
+
+    /// Pairs within range, in descending key order if `reverse`.
+    fn range(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        limit: usize,
+        reverse: bool,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        with_table!(self, |table, snapshot| {
+            let pairs = range_in(&table, start, end, limit, reverse)?;
+            if snapshot {
+                self.reads.lock().push(Read::Range {
+                    start: start.to_vec(),
+                    end: end.to_vec(),
+                    limit,
+                    reverse,
+                    pairs: pairs.clone(),
+                });
+            }
+            Ok(pairs)
+        })
+    }
+}
+
+#[async_trait]
+impl Transaction for RedbTransaction {
+    type KeyIterator = std::vec::IntoIter<Vec<u8>>;
+    type PairIterator = std::vec::IntoIter<(Vec<u8>, Vec<u8>)>;
+
+    async fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        with_table!(self, |table, snapshot| {
+            let value = get_in(&table, key)?;
+            if snapshot {
+                self.reads
+                    .lock()
+                    .push(Read::Key(key.to_vec(), value.clone()));
+            }
+            Ok(value)
+        })
+    }
+
+    async fn get_for_update(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        // write transactions are serialized already
+        self.write().await?;
+        self.get(key).await
+    }
+
+    async fn put(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        let txn = self.write().await?;
+        let mut table = txn.open_table(TABLE).map_err(::redb::Error::from)?;
+        table.insert(key, value).map_err(::redb::Error::from)?;
+        Ok(())
+    }
+
+    async fn delete(&mut self, key: &[u8]) -> Result<()> {
+        let txn = self.write().await?;
+        let mut table = txn.open_table(TABLE).map_err(::redb::Error::from)?;
+        table.remove(key).map_err(::redb::Error::from)?;
+        Ok(())
+    }
+
+    async fn scan(&self, start: &[u8], end: &[u8], limit: usize) -> Result<Self::PairIterator> {
+        Ok(self.range(start, end, limit, false)?.into_iter())
+    }
+
+    async fn scan_keys(&self, start: &[u8], end: &[u8], limit: usize) -> Result<Self::KeyIterator> {
+        let pairs = self.range(start, end, limit, false)?;
+        let keys: Vec<Vec<u8>> = pairs.into_iter().map(|(k, _)| k).collect();
+        Ok(keys.into_iter())
+    }
+
+    async fn scan_rev(&self, start: &[u8], end: &[u8], limit: usize) -> Result<Self::PairIterator> {
+        Ok(self.range(start, end, limit, true)?.into_iter())
+    }
+
+    async fn scan_keys_rev(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        limit: usize,
+    ) -> Result<Self::KeyIterator> {
+        let pairs = self.range(start, end, limit, true)?;
+        let keys: Vec<Vec<u8>> = pairs.into_iter().map(|(k, _)| k).collect();
+        Ok(keys.into_iter())
+    }
+
+    fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    async fn commit(&mut self) -> Result<()> {
+        let txn = self.write_txn.take();
+        // release the write lock only after the commit is done, even if this
+        // future is dropped meanwhile
+        let guard = self.guard.take();
+        self.end();
+        if let Some(txn) = txn {
+            blocking(move || {
+                txn.commit().map_err(::redb::Error::from)?;
+                drop(guard);
+                Ok(())
+            })
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn rollback(&mut self) -> Result<()> {
+        let txn = self.write_txn.take();
+        let result = txn.map(WriteTransaction::abort).transpose();
+        self.end();
+        result.map_err(::redb::Error::from)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        client::tests::check_concurrent_once,
+        error::Error,
+        ops::tests::check_reactors,
+        store::{Direction, Store, Transaction},
+    };
+
+    use super::RedbStore;
+
+    #[tokio::test]
+    async fn test_redb_store() {
+        let mut store = RedbStore::in_memory().await.unwrap();
+
+        let mut txn = store.begin_txn().await.unwrap();
+        assert!(txn.get(b"key").await.unwrap().is_none());
+        txn.put(b"key", b"value").await.unwrap();
+        assert_eq!(txn.get(b"key").await.unwrap().unwrap(), b"value");
+        txn.commit().await.unwrap();
+
+        let mut txn = store.begin_txn().await.unwrap();
+        assert_eq!(txn.get_for_update(b"key").await.unwrap().unwrap(), b"value");
+        txn.put(b"key", b"").await.unwrap();
+        assert_eq!(txn.get(b"key").await.unwrap().unwrap(), b"");
+        txn.commit().await.unwrap();
+
+        let mut txn = store.begin_txn().await.unwrap();
+        txn.put(b"key2", b"v2").await.unwrap();
+        assert_eq!(
+            txn.scan(b"key", b"key3", 10)
+                .await
+                .unwrap()
+                .collect::<Vec<(Vec<u8>, Vec<u8>)>>(),
+            vec![
+                (b"key".to_vec(), b"".to_vec()),
+                (b"key2".to_vec(), b"v2".to_vec())
+            ]
+        );
+        assert_eq!(
+            txn.scan(b"key", b"key3", 1)
+                .await
+                .unwrap()
+                .collect::<Vec<(Vec<u8>, Vec<u8>)>>(),
+            vec![(b"key".to_vec(), b"".to_vec())]
+        );
+        txn.put(b"key4", b"v4").await.unwrap();
+        assert_eq!(
+            txn.scan_keys(b"key", b"key3", 10)
+                .await
+                .unwrap()
+                .collect::<Vec<Vec<u8>>>(),
+            vec![b"key".to_vec(), b"key2".to_vec()]
+        );
+        txn.commit().await.unwrap();
+
+        let mut txn = store.begin_txn().await.unwrap();
+        assert_eq!(txn.get(b"key").await.unwrap().unwrap(), b"");
+        txn.delete(b"key").await.unwrap();
+        assert!(txn.get(b"key").await.unwrap().is_none());
+        assert_eq!(
+            txn.scan_keys_rev(b"key", b"key5", 10)
+                .await
+                .unwrap()
+                .collect::<Vec<Vec<u8>>>(),
+            vec![b"key4".to_vec(), b"key2".to_vec()]
+        );
+        assert_eq!(
+            txn.scan_page(b"key", b"key5", Some(b"key4"), Direction::Backward, 1)
+                .await
+                .unwrap()
+                .collect::<Vec<(Vec<u8>, Vec<u8>)>>(),
+            vec![(b"key2".to_vec(), b"v2".to_vec())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_redb_rollback() {
+        let mut store = RedbStore::in_memory().await.unwrap();
+
+        let mut txn = store.begin_txn().await.unwrap();
+        txn.put(b"key", b"value").await.unwrap();
+        txn.commit().await.unwrap();
+
+        let mut txn = store.begin_txn().await.unwrap();
+        txn.put(b"key", b"changed").await.unwrap();
+        txn.put(b"key2", b"v2").await.unwrap();
+        txn.rollback().await.unwrap();
+        assert_eq!(txn.get(b"key").await.unwrap().unwrap(), b"value");
+        assert!(txn.get(b"key2").await.unwrap().is_none());
+        drop(txn);
+
+        // uncommitted writes are discarded on drop
+        let mut txn = store.begin_txn().await.unwrap();
+        txn.delete(b"key").await.unwrap();
+        drop(txn);
+        let txn = store.begin_txn().await.unwrap();
+        assert_eq!(txn.get(b"key").await.unwrap().unwrap(), b"value");
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_redb_isolation() {
+        let mut store = RedbStore::in_memory().await.unwrap();
+
+        // reads do not wait for a pending write transaction
+        let mut writer = store.begin_txn().await.unwrap();
+        writer.put(b"count", &[0]).await.unwrap();
+        let reader = store.begin_txn().await.unwrap();
+        assert!(reader.get(b"count").await.unwrap().is_none());
+        writer.commit().await.unwrap();
+        // reader keeps its snapshot
+        assert!(reader.get(b"count").await.unwrap().is_none());
+        drop(reader);
+
+        // reads from the snapshot are checked once writing
+        let mut txn1 = store.begin_txn().await.unwrap();
+        let mut txn2 = store.begin_txn().await.unwrap();
+        assert_eq!(txn1.get(b"count").await.unwrap().unwrap(), [0]);
+        assert!(txn1.scan(b"a", b"b", 10).await.unwrap().next().is_none());
+        txn2.put(b"other", &[0]).await.unwrap();
+        txn2.commit().await.unwrap();
+        txn1.put(b"count", &[1]).await.unwrap();
+        txn1.commit().await.unwrap();
+        let mut txn1 = store.begin_txn().await.unwrap();
+        let mut txn2 = store.begin_txn().await.unwrap();
+        assert_eq!(txn1.get(b"count").await.unwrap().unwrap(), [1]);
+        txn2.put(b"count", &[0]).await.unwrap();
+        txn2.commit().await.unwrap();
+        assert!(matches!(
+            txn1.get_for_update(b"count").await,
+            Err(Error::TransactionConflict)
+        ));
+        drop((txn1, txn2));
+
+        // interleaved writers wait for each other, yielding to the runtime
+        let increment = |mut store: RedbStore| async move {
+            for _ in 0..25 {
+                let mut txn = store.begin_txn().await.unwrap();
+                let count = txn
+                    .get_for_update(b"count")
+                    .await
+                    .unwrap()
+                    .map_or(0, |v| v[0]);
+                tokio::task::yield_now().await;
+                txn.put(b"count", &[count + 1]).await.unwrap();
+                tokio::task::yield_now().await;
+                txn.commit().await.unwrap();
+            }
+        };
+        tokio::join!(increment(store.clone()), increment(store.clone()));
+
+        let txn = store.begin_txn().await.unwrap();
+        assert_eq!(txn.get(b"count").await.unwrap().unwrap(), [50]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_redb_concurrent_once() {
+        check_concurrent_once(RedbStore::in_memory().await.unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_redb_reactors() {
+        check_reactors(|| async { RedbStore::in_memory().await.unwrap() }).await;
+    }
+
+    #[tokio::test]
+    async fn test_redb_persistence() {
+        let path = std::env::temp_dir().join(format!("rcommunity-{}.redb", uuid::Uuid::new_v4()));
+
+        let mut store = RedbStore::open(&path).await.unwrap();
+        let mut txn = store.begin_txn().await.unwrap();
+        txn.put(b"key", b"value").await.unwrap();
+        txn.commit().await.unwrap();
+        drop(txn);
+        drop(store);
+
+        let mut store = RedbStore::open(&path).await.unwrap();
+        let txn = store.begin_txn().await.unwrap();
+        assert_eq!(txn.get(b"key").await.unwrap().unwrap(), b"value");
+        drop(txn);
+        drop(store);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
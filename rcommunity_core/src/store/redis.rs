@@ -0,0 +1,334 @@
+//! A Redis-backed [`Store`] implementation, enabled by feature `redis`.
+//!
+//! # Example Usage
+//! ```rust,no_run
+//! use rcommunity_core::store::redis::RedisStore;
+//! use rcommunity_core::store::{Store, Transaction};
+//!
+//! tokio_test::block_on(async {
+//!     let mut store = RedisStore::open("redis://127.0.0.1/", "community")
+//!         .await
+//!         .unwrap();
+//!     let mut txn = store.begin_txn().await.unwrap();
+//!     txn.put(b"key", b"value").await.unwrap();
+//!     txn.commit().await.unwrap();
+//! })
+//! ```
+
+use std::collections::BTreeMap;
+
+use ::redis::{aio::MultiplexedConnection, AsyncCommands, Client};
+use async_trait::async_trait;
+
+use crate::error::{Error, Result};
+
+use super::{format::Codec, migration::check_version, Store, Transaction};
+
+/// Times to retry committing a transaction aborted by changes to watched keys
+/// that left their values untouched.
+const MAX_COMMIT_RETRIES: usize = 3;
+
+/// Implementation of a Redis-backed [`Store`].
+///
+/// Each key-value pair is stored as a Redis string under the key prefixed by
+/// `{namespace}:data:`. Since Redis has no ordered keyspace, all keys are also
+/// added to a sorted set `{namespace}:index` with equal scores, so that range
+/// scans are served by `ZRANGEBYLEX`.
+///
+/// Each transaction runs on its own connection. Writes are buffered until
+/// commit, which applies them atomically with `MULTI/EXEC`. Keys read by
+/// [`Transaction::get_for_update`] are `WATCH`ed, and the commit fails with
+/// [`Error::TransactionConflict`] if any of them is changed by others.
+#[derive(Debug, Clone)]
+pub struct RedisStore {
+    client: Client,
+    namespace: String,
+    codec: Codec,
+}
+
+impl RedisStore {
+    /// Open a store on the Redis server at `url`, keeping all keys under
+    /// `namespace`.
+    ///
+    /// # Errors
+    /// Will return `Err` if server is unreachable, or store is written in
+    /// another format version than the current one, see [`check_version`].
+    pub async fn open(url: &str, namespace: &str) -> Result<Self> {
+        let mut store = Self::open_unchecked(url, namespace)?;
+        check_version(&mut store).await?;
+        Ok(store)
+    }
+
+    /// Open a store like [`RedisStore::open`], without checking its format
+    /// version, so that it can be upgraded by a [`Migrator`].
+    ///
+    /// # Errors
+    /// Will return `Err` if `url` is invalid.
+    ///
+    /// [`Migrator`]: super::migration::Migrator
+    pub fn open_unchecked(url: &str, namespace: &str) -> Result<Self> {
+        Ok(Self {
+            client: Client::open(url)?,
+            namespace: namespace.to_string(),
+            codec: Codec::default(),
+        })
+    }
+
+    /// Write values with `codec`.
+    #[must_use]
+    pub fn with_codec(self, codec: Codec) -> Self {
+        Self { codec, ..self }
+    }
+}
+
+#[async_trait]
+impl Store for RedisStore {
+    type Transaction = RedisTransaction;
+    async fn begin_txn(&mut self) -> Result<RedisTransaction> {
+        let mut data_prefix = self.namespace.as_bytes().to_vec();
+        data_prefix.extend_from_slice(b":data:");
+        Ok(RedisTransaction {
+            con: self.client.get_multiplexed_async_connection().await?,
+            data_prefix,
+            index: format!("{}:index", self.namespace),
+            codec: self.codec,
+            watched: BTreeMap::new(),
+            writes: BTreeMap::new(),
+        })
+    }
+}
+
+/// Transaction type for [`RedisStore`].
+pub struct RedisTransaction {
+    con: MultiplexedConnection,
+    data_prefix: Vec<u8>,
+    index: String,
+    codec: Codec,
+    /// Watched keys, with values read when watching.
+    watched: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+    /// Buffered writes, with `None` for deletion.
+    writes: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+}
+
+impl RedisTransaction {
+    fn data_key(&self, key: &[u8]) -> Vec<u8> {
+        [&self.data_prefix, key].concat()
+    }
+
+    /// Watch all watched keys again, returning whether their values are still
+    /// the same as first read.
+    async fn rewatch(&mut self) -> Result<bool> {
+        let mut con = self.con.clone();
+        for (key, value) in &self.watched {
+            let data_key = self.data_key(key);
+            ::redis::cmd("WATCH")
+                .arg(&data_key)
+                .query_async::<_, ()>(&mut con)
+                .await?;
+            let current: Option<Vec<u8>> = con.get(&data_key).await?;
+            if &current != value {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    async fn exec(&mut self) -> Result<bool> {
+        let mut pipe = ::redis::pipe();
+        pipe.atomic();
+        for (key, value) in &self.writes {
+            let data_key = self.data_key(key);
+            if let Some(value) = value {
+                pipe.set(data_key, value).ignore();
+                pipe.zadd(&self.index, key, 0).ignore();
+            } else {
+                pipe.del(data_key).ignore();
+                pipe.zrem(&self.index, key).ignore();
+            }
+        }
+        let result: Option<()> = pipe.query_async(&mut self.con).await?;
+        Ok(result.is_some())
+    }
+
+    async fn unwatch(&mut self) -> Result<()> {
+        if !self.watched.is_empty() {
+            ::redis::cmd("UNWATCH")
+                .query_async::<_, ()>(&mut self.con)
+                .await?;
+            self.watched.clear();
+        }
+        Ok(())
+    }
+
+    /// Keys in index within range, in descending order if `reverse`, leaving
+    /// room for buffered writes that may shadow them.
+    async fn scan_index(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        limit: usize,
+        reverse: bool,
+    ) -> Result<Vec<Vec<u8>>> {
+        let shadowed = self.writes.range(start.to_vec()..end.to_vec()).count();
+        let count = isize::try_from(limit.saturating_add(shadowed)).unwrap_or(-1);
+        let min = [b"[", start].concat();
+        let max = [b"(", end].concat();
+        let mut con = self.con.clone();
+        if reverse {
+            Ok(con
+                .zrevrangebylex_limit(&self.index, max, min, 0, count)
+                .await?)
+        } else {
+            Ok(con
+                .zrangebylex_limit(&self.index, min, max, 0, count)
+                .await?)
+        }
+    }
+
+    /// Pairs within range as seen by this transaction, in descending key
+    /// order if `reverse`.
+    async fn pairs(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        limit: usize,
+        reverse: bool,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let keys = self.scan_index(start, end, limit, reverse).await?;
+        let values: Vec<Option<Vec<u8>>> = if keys.is_empty() {
+            Vec::new()
+        } else {
+            let data_keys: Vec<_> = keys.iter().map(|k| self.data_key(k)).collect();
+            let mut con = self.con.clone();
+            ::redis::cmd("MGET")
+                .arg(data_keys)
+                .query_async(&mut con)
+                .await?
+        };
+        let merged = self.merge(start, end, keys.into_iter().zip(values));
+        let pairs = merged.into_iter().filter_map(|(k, v)| Some((k, v?)));
+        Ok(if reverse {
+            pairs.rev().take(limit).collect()
+        } else {
+            pairs.take(limit).collect()
+        })
+    }
+
+    /// Keys within range as seen by this transaction, in descending order if
+    /// `reverse`.
+    async fn keys(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        limit: usize,
+        reverse: bool,
+    ) -> Result<Vec<Vec<u8>>> {
+        let keys = self.scan_index(start, end, limit, reverse).await?;
+        let merged = self.merge(start, end, keys.into_iter().map(|k| (k, Some(Vec::new()))));
+        let keys = merged.into_iter().filter_map(|(k, v)| v.map(|_| k));
+        Ok(if reverse {
+            keys.rev().take(limit).collect()
+        } else {
+            keys.take(limit).collect()
+        })
+    }
+
+    /// Merge keys from store with buffered writes in range.
+    fn merge(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        pairs: impl Iterator<Item = (Vec<u8>, Option<Vec<u8>>)>,
+    ) -> BTreeMap<Vec<u8>, Option<Vec<u8>>> {
+        let mut merged: BTreeMap<_, _> = pairs.collect();
+        for (key, value) in self.writes.range(start.to_vec()..end.to_vec()) {
+            merged.insert(key.clone(), value.clone());
+        }
+        merged
+    }
+}
+
+#[async_trait]
+impl Transaction for RedisTransaction {
+    type KeyIterator = std::vec::IntoIter<Vec<u8>>;
+    type PairIterator = std::vec::IntoIter<(Vec<u8>, Vec<u8>)>;
+
+    async fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        if let Some(value) = self.writes.get(key) {
+            return Ok(value.clone());
+        }
+        let mut con = self.con.clone();
+        Ok(con.get(self.data_key(key)).await?)
+    }
+
+    async fn get_for_update(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let data_key = self.data_key(key);
+        ::redis::cmd("WATCH")
+            .arg(&data_key)
+            .query_async::<_, ()>(&mut self.con)
+            .await?;
+        let value: Option<Vec<u8>> = self.con.get(&data_key).await?;
+        self.watched.insert(key.to_vec(), value.clone());
+        if let Some(value) = self.writes.get(key) {
+            return Ok(value.clone());
+        }
+        Ok(value)
+    }
+
+    async fn put(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.writes.insert(key.to_vec(), Some(value.to_vec()));
+        Ok(())
+    }
+
+    async fn delete(&mut self, key: &[u8]) -> Result<()> {
+        self.writes.insert(key.to_vec(), None);
+        Ok(())
+    }
+
+    async fn scan(&self, start: &[u8], end: &[u8], limit: usize) -> Result<Self::PairIterator> {
+        Ok(self.pairs(start, end, limit, false).await?.into_iter())
+    }
+
+    async fn scan_keys(&self, start: &[u8], end: &[u8], limit: usize) -> Result<Self::KeyIterator> {
+        Ok(self.keys(start, end, limit, false).await?.into_iter())
+    }
+
+    async fn scan_rev(&self, start: &[u8], end: &[u8], limit: usize) -> Result<Self::PairIterator> {
+        Ok(self.pairs(start, end, limit, true).await?.into_iter())
+    }
+
+    async fn scan_keys_rev(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        limit: usize,
+    ) -> Result<Self::KeyIterator> {
+        Ok(self.keys(start, end, limit, true).await?.into_iter())
+    }
+
+    fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    async fn commit(&mut self) -> Result<()> {
+        let mut retries = 0;
+        // an aborted `EXEC` unwatches all keys, which are watched again before
+        // retrying if their values are unchanged
+        while !self.exec().await? {
+            if retries == MAX_COMMIT_RETRIES || !self.rewatch().await? {
+                self.unwatch().await?;
+                self.writes.clear();
+                return Err(Error::TransactionConflict);
+            }
+            retries += 1;
+        }
+        self.watched.clear();
+        self.writes.clear();
+        Ok(())
+    }
+
+    async fn rollback(&mut self) -> Result<()> {
+        self.writes.clear();
+        self.unwatch().await
+    }
+}
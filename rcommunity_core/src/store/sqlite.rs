@@ -0,0 +1,626 @@
+//! A SQLite-backed [`Store`] implementation, enabled by feature `sqlite`.
+//!
+//! # Example Usage
+//! ```rust
+//! use rcommunity_core::store::sqlite::SqliteStore;
+//! use rcommunity_core::store::{Store, Transaction};
+//!
+//! tokio_test::block_on(async {
+//!     let mut store = SqliteStore::in_memory().await.unwrap();
+//!     let mut txn = store.begin_txn().await.unwrap();
+//!     txn.put(b"key", b"value").await.unwrap();
+//!     txn.commit().await.unwrap();
+//!     let txn = store.begin_txn().await.unwrap();
+//!     assert_eq!(
+//!         txn.get(b"key").await.unwrap().unwrap(),
+//!         b"value",
+//!     );
+//! })
+//! ```
+
+use std::{path::Path, sync::Arc, time::Duration};
+
+use ::rusqlite::{params, Connection, OptionalExtension};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+
+use crate::error::Result;
+
+use super::{blocking, format::Codec, migration::check_version, Store, Transaction};
+
+/// Time to wait for locks held by other transactions.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Implementation of a SQLite-backed [`Store`].
+///
+/// All key-value pairs are kept in a single table
+/// `rcommunity (key BLOB PRIMARY KEY, value BLOB)`. Each transaction runs on
+/// its own connection, and begins a SQL transaction on first use: reads begin a
+/// deferred one, while [`Transaction::get_for_update`] and writes begin with
+/// `BEGIN IMMEDIATE`, taking the database write lock. A transaction that has
+/// only read so far takes the write lock on its first
+/// [`Transaction::get_for_update`] or write, and fails with
+/// [`Error::TransactionConflict`] if others have written since, as its reads
+/// may be stale. So does failing to take a lock within a timeout. Database
+/// calls run on the blocking thread pool of tokio, so waiting for a lock does
+/// not block the runtime, and the store must be used within a tokio runtime.
+///
+/// Transactions begun by [`Store::begin_optimistic_txn`], as in
+/// [`Store::run_in_txn`], are expected to write, so they begin with
+/// `BEGIN IMMEDIATE` even to read, and never have to take the write lock
+/// later. They only fail to take it within a timeout, which is retried.
+///
+/// [`Error::TransactionConflict`]: crate::error::Error::TransactionConflict
+#[derive(Debug, Clone)]
+pub struct SqliteStore {
+    path: String,
+    /// Connection keeping an in-memory database alive.
+    _keepalive: Option<Arc<Mutex<Connection>>>,
+    codec: Codec,
+}
+
+impl SqliteStore {
+    /// Open a store in database file at `path`, creating it if not existing.
+    ///
+    /// # Errors
+    /// Will return `Err` if database cannot be opened, or store is written in
+    /// another format version than the current one, see [`check_version`].
+    pub async fn open(path: impl AsRef<Path> + Send) -> Result<Self> {
+        let mut store = Self::open_unchecked(path).await?;
+        check_version(&mut store).await?;
+        Ok(store)
+    }
+
+    /// Open a store at `path` like [`SqliteStore::open`], without checking
+    /// its format version, so that it can be upgraded by a [`Migrator`].
+    ///
+    /// # Errors
+    /// Will return `Err` if database cannot be opened.
+    ///
+    /// [`Migrator`]: super::migration::Migrator
+    pub async fn open_unchecked(path: impl AsRef<Path> + Send) -> Result<Self> {
+        let path = path.as_ref().to_string_lossy().into_owned();
+        let con = blocking({
+            let path = path.clone();
+            move || {
+                let con = Connection::open(path)?;
+                // let readers proceed while a transaction is writing
+                con.pragma_update(None, "journal_mode", "WAL")?;
+                Ok(con)
+            }
+        })
+        .await?;
+        Self::new(path, con, false).await
+    }
+
+    /// Open a store in a new in-memory database, shared by all its
+    /// transactions.
+    ///
+    /// # Errors
+    /// Will return `Err` if database cannot be created.
+    pub async fn in_memory() -> Result<Self> {
+        let path = format!("file:/rcommunity-{}?vfs=memdb", uuid::Uuid::new_v4());
+        let con = blocking({
+            let path = path.clone();
+            move || Ok(Connection::open(path)?)
+        })
+        .await?;
+        let mut store = Self::new(path, con, true).await?;
+        check_version(&mut store).await?;
+        Ok(store)
+    }
+
+    async fn new(path: String, con: Connection, keepalive: bool) -> Result<Self> {
+        let con = blocking(move || {
+            con.execute(
+                "CREATE TABLE IF NOT EXISTS rcommunity (key BLOB PRIMARY KEY, value BLOB NOT NULL)",
+                [],
+            )?;
+            Ok(con)
+        })
+        .await?;
+        Ok(Self {
+            path,
+            _keepalive: keepalive.then(|| Arc::new(Mutex::new(con))),
+            codec: Codec::default(),
+        })
+    }
+
+    /// Write values with `codec`.
+    #[must_use]
+    pub fn with_codec(self, codec: Codec) -> Self {
+        Self { codec, ..self }
+    }
+}
+
+#[async_trait]
+impl Store for SqliteStore {
+    type Transaction = SqliteTransaction;
+    async fn begin_txn(&mut self) -> Result<SqliteTransaction> {
+        let path = self.path.clone();
+        let con = blocking(move || {
+            let con = Connection::open(path)?;
+            con.busy_timeout(BUSY_TIMEOUT)?;
+            Ok(con)
+        })
+        .await?;
+        Ok(SqliteTransaction {
+            session: Arc::new(Mutex::new(Session {
+                con,
+                state: State::Idle,
+            })),
+            write: false,
+            codec: self.codec,
+        })
+    }
+    async fn begin_optimistic_txn(&mut self) -> Result<SqliteTransaction> {
+        let mut txn = self.begin_txn().await?;
+        txn.write = true;
+        Ok(txn)
+    }
+}
+
+#[derive(PartialEq, Eq)]
+enum State {
+    /// No SQL transaction has begun.
+    Idle,
+    /// A deferred SQL transaction has begun, and only read so far.
+    Read,
+    /// An immediate SQL transaction has begun, holding the write lock.
+    Write,
+}
+
+struct Session {
+    con: Connection,
+    state: State,
+}
+
+impl Session {
+    fn begin(&mut self, write: bool) -> Result<&Connection> {
+        match self.state {
+            State::Idle if write => {
+                self.con.execute_batch("BEGIN IMMEDIATE")?;
+                self.state = State::Write;
+            }
+            State::Idle => {
+                self.con.execute_batch("BEGIN")?;
+                self.state = State::Read;
+            }
+            State::Read if write => {
+                // take the write lock with an empty write, which fails if
+                // another transaction has written since the reads began
+                if let Err(e) = self.con.execute_batch("DELETE FROM rcommunity WHERE 0") {
+                    self.end("ROLLBACK")?;
+                    return Err(e.into());
+                }
+                self.state = State::Write;
+            }
+            State::Read | State::Write => {}
+        }
+        Ok(&self.con)
+    }
+
+    fn end(&mut self, statement: &str) -> Result<()> {
+        if self.state == State::Idle {
+            return Ok(());
+        }
+        self.state = State::Idle;
+        let result = self.con.execute_batch(statement);
+        if result.is_err() && !self.con.is_autocommit() {
+            // a failed commit leaves the transaction open
+            self.con.execute_batch("ROLLBACK")?;
+        }
+        Ok(result?)
+    }
+}
+
+/// Transaction type for [`SqliteStore`].
+///
+/// Uncommitted writes are rolled back when dropped.
+pub struct SqliteTransaction {
+    session: Arc<Mutex<Session>>,
+    /// Whether to take the write lock on first use.
+    write: bool,
+    codec: Codec,
+}
+
+impl SqliteTransaction {
+    /// Run `f` with the session on the blocking thread pool.
+    async fn run<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Session) -> Result<R> + Send + 'static,
+    ) -> Result<R> {
+        let session = self.session.clone();
+        blocking(move || f(&mut session.lock())).await
+    }
+
+    async fn range<T: Send + 'static>(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        limit: usize,
+        sql: &'static str,
+        f: impl Fn(&::rusqlite::Row<'_>) -> ::rusqlite::Result<T> + Send + 'static,
+    ) -> Result<Vec<T>> {
+        let (start, end) = (start.to_vec(), end.to_vec());
+        // negative limit means no limit
+        let limit = i64::try_from(limit).unwrap_or(-1);
+        let write = self.write;
+        self.run(move |session| {
+            let con = session.begin(write)?;
+            let mut stmt = con.prepare_cached(sql)?;
+            let rows = stmt.query_map(params![start, end, limit], f)?;
+            Ok(rows.collect::<::rusqlite::Result<_>>()?)
+        })
+        .await
+    }
+
+    async fn get_with(&self, key: &[u8], write: bool) -> Result<Option<Vec<u8>>> {
+        let key = key.to_vec();
+        let write = write || self.write;
+        self.run(move |session| {
+            let con = session.begin(write)?;
+            let value = con
+                .prepare_cached("SELECT value FROM rcommunity WHERE key = ?1")?
+                .query_row([key], |row| row.get(0))
+                .optional()?;
+            Ok(value)
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl Transaction for SqliteTransaction {
+    type KeyIterator = std::vec::IntoIter<Vec<u8>>;
+    type PairIterator = std::vec::IntoIter<(Vec<u8>, Vec<u8>)>;
+
+    async fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.get_with(key, false).await
+    }
+
+    async fn get_for_update(&mut self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.get_with(key, true).await
+    }
+
+    async fn put(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        let (key, value) = (key.to_vec(), value.to_vec());
+        self.run(move |session| {
+            let con = session.begin(true)?;
+            con.prepare_cached("INSERT OR REPLACE INTO rcommunity (key, value) VALUES (?1, ?2)")?
+                .execute(params![key, value])?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete(&mut self, key: &[u8]) -> Result<()> {
+        let key = key.to_vec();
+        self.run(move |session| {
+            let con = session.begin(true)?;
+            con.prepare_cached("DELETE FROM rcommunity WHERE key = ?1")?
+                .execute([key])?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn scan(&self, start: &[u8], end: &[u8], limit: usize) -> Result<Self::PairIterator> {
+        let pairs = self
+            .range(
+                start,
+                end,
+                limit,
+                "SELECT key, value FROM rcommunity WHERE key >= ?1 AND key < ?2 ORDER BY key LIMIT ?3",
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .await?;
+        Ok(pairs.into_iter())
+    }
+
+    async fn scan_keys(&self, start: &[u8], end: &[u8], limit: usize) -> Result<Self::KeyIterator> {
+        let keys = self
+            .range(
+                start,
+                end,
+                limit,
+                "SELECT key FROM rcommunity WHERE key >= ?1 AND key < ?2 ORDER BY key LIMIT ?3",
+                |row| row.get(0),
+            )
+            .await?;
+        Ok(keys.into_iter())
+    }
+
+    async fn scan_rev(&self, start: &[u8], end: &[u8], limit: usize) -> Result<Self::PairIterator> {
+        let pairs = self
+            .range(
+                start,
+                end,
+                limit,
+                "SELECT key, value FROM rcommunity WHERE key >= ?1 AND key < ?2 ORDER BY key DESC LIMIT ?3",
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .await?;
+        Ok(pairs.into_iter())
+    }
+
+    async fn scan_keys_rev(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        limit: usize,
+    ) -> Result<Self::KeyIterator> {
+        let keys = self
+            .range(
+                start,
+                end,
+                limit,
+                "SELECT key FROM rcommunity WHERE key >= ?1 AND key < ?2 ORDER BY key DESC LIMIT ?3",
+                |row| row.get(0),
+            )
+            .await?;
+        Ok(keys.into_iter())
+    }
+
+    fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    async fn commit(&mut self) -> Result<()> {
+        self.run(|session| session.end("COMMIT")).await
+    }
+
+    async fn rollback(&mut self) -> Result<()> {
+        self.run(|session| session.end("ROLLBACK")).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        client::tests::check_concurrent_once,
+        error::{Error, Result},
+        ops::tests::check_reactors,
+        store::{
+            format::to_key,
+            migration::{Migration, Migrator},
+            Direction, Store, Transaction,
+        },
+    };
+
+    use super::SqliteStore;
+
+    #[tokio::test]
+    async fn test_sqlite_store() {
+        let mut store = SqliteStore::in_memory().await.unwrap();
+
+        let mut txn = store.begin_txn().await.unwrap();
+        assert!(txn.get(b"key").await.unwrap().is_none());
+        txn.put(b"key", b"value").await.unwrap();
+        assert_eq!(txn.get(b"key").await.unwrap().unwrap(), b"value");
+        txn.commit().await.unwrap();
+
+        let mut txn = store.begin_txn().await.unwrap();
+        assert_eq!(txn.get_for_update(b"key").await.unwrap().unwrap(), b"value");
+        txn.put(b"key", b"").await.unwrap();
+        assert_eq!(txn.get(b"key").await.unwrap().unwrap(), b"");
+        txn.commit().await.unwrap();
+
+        let mut txn = store.begin_txn().await.unwrap();
+        txn.put(b"key2", b"v2").await.unwrap();
+        assert_eq!(
+            txn.scan(b"key", b"key3", 10)
+                .await
+                .unwrap()
+                .collect::<Vec<(Vec<u8>, Vec<u8>)>>(),
+            vec![
+                (b"key".to_vec(), b"".to_vec()),
+                (b"key2".to_vec(), b"v2".to_vec())
+            ]
+        );
+        assert_eq!(
+            txn.scan(b"key", b"key3", 1)
+                .await
+                .unwrap()
+                .collect::<Vec<(Vec<u8>, Vec<u8>)>>(),
+            vec![(b"key".to_vec(), b"".to_vec())]
+        );
+        txn.put(b"key4", b"v4").await.unwrap();
+        assert_eq!(
+            txn.scan_keys(b"key", b"key3", usize::MAX)
+                .await
+                .unwrap()
+                .collect::<Vec<Vec<u8>>>(),
+            vec![b"key".to_vec(), b"key2".to_vec()]
+        );
+        assert_eq!(
+            txn.scan_rev(b"key", b"key5", 2)
+                .await
+                .unwrap()
+                .collect::<Vec<(Vec<u8>, Vec<u8>)>>(),
+            vec![
+                (b"key4".to_vec(), b"v4".to_vec()),
+                (b"key2".to_vec(), b"v2".to_vec())
+            ]
+        );
+        assert_eq!(
+            txn.scan_keys_page(b"key", b"key5", Some(b"key2"), Direction::Backward, 10)
+                .await
+                .unwrap()
+                .collect::<Vec<Vec<u8>>>(),
+            vec![b"key".to_vec()]
+        );
+        txn.commit().await.unwrap();
+
+        let mut txn = store.begin_txn().await.unwrap();
+        assert_eq!(txn.get(b"key").await.unwrap().unwrap(), b"");
+        txn.delete(b"key").await.unwrap();
+        assert!(txn.get(b"key").await.unwrap().is_none());
+        txn.rollback().await.unwrap();
+        assert_eq!(txn.get(b"key").await.unwrap().unwrap(), b"");
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_file() {
+        let path = std::env::temp_dir().join(format!("rcommunity-{}.db", uuid::Uuid::new_v4()));
+
+        let mut store = SqliteStore::open(&path).await.unwrap();
+        let mut txn = store.begin_txn().await.unwrap();
+        txn.put(b"key", b"value").await.unwrap();
+        txn.commit().await.unwrap();
+
+        // readers see committed data only, while writers wait for each other
+        let mut txn1 = store.begin_txn().await.unwrap();
+        let mut txn2 = store.begin_txn().await.unwrap();
+        assert_eq!(
+            txn1.get_for_update(b"key").await.unwrap().unwrap(),
+            b"value"
+        );
+        txn1.put(b"key", b"changed").await.unwrap();
+        assert_eq!(txn2.get(b"key").await.unwrap().unwrap(), b"value");
+        txn1.commit().await.unwrap();
+        txn2.commit().await.unwrap();
+        assert_eq!(txn2.get(b"key").await.unwrap().unwrap(), b"changed");
+        drop(txn2);
+
+        // uncommitted writes are discarded on drop
+        let mut txn = store.begin_txn().await.unwrap();
+        txn.delete(b"key").await.unwrap();
+        drop(txn);
+        drop(store);
+
+        let mut store = SqliteStore::open(&path).await.unwrap();
+        let txn = store.begin_txn().await.unwrap();
+        assert_eq!(txn.get(b"key").await.unwrap().unwrap(), b"changed");
+        drop(txn);
+        drop(store);
+        for suffix in ["", "-wal", "-shm"] {
+            let mut file = path.clone().into_os_string();
+            file.push(suffix);
+            let _ = std::fs::remove_file(file);
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_sqlite_upgrade() {
+        let path = std::env::temp_dir().join(format!("rcommunity-{}.db", uuid::Uuid::new_v4()));
+        let mut store = SqliteStore::open(&path).await.unwrap();
+
+        // a reader takes the write lock in place, unless another transaction
+        // has written since it began reading
+        let mut txn1 = store.begin_txn().await.unwrap();
+        assert!(txn1.get(b"count").await.unwrap().is_none());
+        txn1.put(b"count", &[1]).await.unwrap();
+        txn1.commit().await.unwrap();
+        let mut txn1 = store.begin_txn().await.unwrap();
+        let mut txn2 = store.begin_txn().await.unwrap();
+        assert_eq!(txn2.get(b"count").await.unwrap().unwrap(), [1]);
+        txn1.put(b"count", &[2]).await.unwrap();
+        txn1.commit().await.unwrap();
+        assert!(matches!(
+            txn2.get_for_update(b"count").await,
+            Err(Error::TransactionConflict)
+        ));
+        drop((txn1, txn2));
+
+        // interleaved read-then-update transactions in `run_in_txn` take the
+        // write lock up front, yielding to the runtime while waiting for it
+        let increment = |mut store: SqliteStore| async move {
+            for _ in 0..25 {
+                store
+                    .run_in_txn(|mut txn| async move {
+                        let result = async {
+                            txn.get(b"count").await?;
+                            tokio::task::yield_now().await;
+                            let count = txn.get_for_update(b"count").await?.unwrap()[0];
+                            tokio::task::yield_now().await;
+                            txn.put(b"count", &[count + 1]).await
+                        }
+                        .await;
+                        (txn, result)
+                    })
+                    .await
+                    .unwrap();
+            }
+        };
+        tokio::join!(increment(store.clone()), increment(store.clone()));
+
+        let txn = store.begin_txn().await.unwrap();
+        assert_eq!(txn.get(b"count").await.unwrap().unwrap(), [52]);
+        drop(txn);
+        drop(store);
+        for suffix in ["", "-wal", "-shm"] {
+            let mut file = path.clone().into_os_string();
+            file.push(suffix);
+            let _ = std::fs::remove_file(file);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_reactors() {
+        check_reactors(|| async { SqliteStore::in_memory().await.unwrap() }).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_sqlite_concurrent_once() {
+        check_concurrent_once(SqliteStore::in_memory().await.unwrap()).await;
+    }
+
+    /// Upgrades keys of a legacy `Legacy:value` layout.
+    struct Legacy;
+
+    impl Migration for Legacy {
+        fn source_version(&self) -> u32 {
+            1
+        }
+
+        fn migrate(&self, key: &[u8], value: &[u8]) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+            let key = std::str::from_utf8(key).unwrap();
+            Ok(key
+                .strip_prefix("Legacy:")
+                .map(|rest| (to_key(&("Current", rest)).unwrap(), value.to_vec())))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_migration() {
+        let path = std::env::temp_dir().join(format!("rcommunity-{}.db", uuid::Uuid::new_v4()));
+
+        // a store written before versions were recorded
+        let mut store = SqliteStore::open_unchecked(&path).await.unwrap();
+        let mut txn = store.begin_txn().await.unwrap();
+        txn.put(b"Legacy:a", b"v").await.unwrap();
+        txn.commit().await.unwrap();
+        drop(txn);
+        drop(store);
+
+        assert!(matches!(
+            SqliteStore::open(&path).await,
+            Err(Error::MigrationRequired { found: 1, .. })
+        ));
+
+        let mut store = SqliteStore::open_unchecked(&path).await.unwrap();
+        assert_eq!(
+            Migrator::new()
+                .register(Legacy)
+                .run(&mut store)
+                .await
+                .unwrap(),
+            1
+        );
+        drop(store);
+
+        let mut store = SqliteStore::open(&path).await.unwrap();
+        let txn = store.begin_txn().await.unwrap();
+        let key = to_key(&("Current", "a")).unwrap();
+        assert_eq!(txn.get(&key).await.unwrap().unwrap(), b"v");
+        assert!(txn.get(b"Legacy:a").await.unwrap().is_none());
+        drop(txn);
+        drop(store);
+        for suffix in ["", "-wal", "-shm"] {
+            let mut file = path.clone().into_os_string();
+            file.push(suffix);
+            let _ = std::fs::remove_file(file);
+        }
+    }
+}
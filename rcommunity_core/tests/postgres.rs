@@ -0,0 +1,323 @@
+//! Integration tests of [`PgStore`] against a local Postgres server.
+//!
+//! A server is initialized with `initdb` in a temporary directory and started
+//! with `pg_ctl` for each test, or set `PG_CONFIG` to test against a running
+//! one. When run as root, the server runs as user `PG_USER`, defaulting to
+//! `postgres`. The tests are ignored by default, run them with
+//! `cargo test --features postgres -- --ignored`. They fail if no server is
+//! available.
+#![cfg(feature = "postgres")]
+
+use std::{
+    net::TcpListener,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+use rcommunity_core::{
+    client::{ItemReactionClient, UserItemUnboundedReactionClient},
+    error::Error,
+    ops::Aggregate,
+    store::{migration::FORMAT_VERSION, postgres::PgStore, Direction, Store, Transaction},
+    ItemType, Numerical, Once, ReactionType, UserType, ID,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct User(usize);
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Item(usize);
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Vote(i64);
+
+impl UserType for User {}
+impl ID for User {}
+impl ItemType for Item {}
+impl ID for Item {}
+impl ReactionType for Vote {}
+impl Once for Vote {}
+impl Numerical for Vote {
+    type Item = i64;
+    fn value(&self) -> i64 {
+        self.0
+    }
+}
+
+struct Server {
+    config: String,
+    data_dir: Option<PathBuf>,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        if let Some(data_dir) = &self.data_dir {
+            let data_dir = data_dir.to_string_lossy();
+            let _ = pg_command(&["pg_ctl", "-D", &data_dir, "-m", "immediate", "stop"]);
+            let _ = std::fs::remove_dir_all(&*data_dir);
+        }
+    }
+}
+
+/// Run a Postgres command, as an unprivileged user if run as root.
+fn pg_command(args: &[&str]) -> Option<Output> {
+    let is_root = Command::new("id")
+        .arg("-u")
+        .output()
+        .is_ok_and(|output| output.stdout.trim_ascii() == b"0");
+    let mut command = if is_root {
+        let user = std::env::var("PG_USER").unwrap_or_else(|_| "postgres".to_string());
+        let mut command = Command::new("runuser");
+        command.args(["-u", &user, "--"]);
+        command
+    } else {
+        Command::new(args[0])
+    };
+    if is_root {
+        command.arg(args[0]);
+    }
+    let output = command
+        .args(&args[1..])
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+    output.status.success().then_some(output)
+}
+
+fn spawn_server() -> Server {
+    try_spawn_server().expect("cannot start Postgres server, install it or set PG_CONFIG")
+}
+
+fn try_spawn_server() -> Option<Server> {
+    if let Ok(config) = std::env::var("PG_CONFIG") {
+        return Some(Server {
+            config,
+            data_dir: None,
+        });
+    }
+    let port = TcpListener::bind("127.0.0.1:0")
+        .ok()?
+        .local_addr()
+        .ok()?
+        .port();
+    let data_dir = std::env::temp_dir().join(format!("rcommunity-pg-{}", uuid::Uuid::new_v4()));
+    let dir = data_dir.to_string_lossy().into_owned();
+    pg_command(&["initdb", "-D", &dir, "-A", "trust", "-U", "postgres"])?;
+    let server = Server {
+        config: format!("host={dir} port={port} user=postgres dbname=postgres"),
+        data_dir: Some(data_dir),
+    };
+    let options = format!("-p {port} -k {dir} -c listen_addresses=''");
+    let log = format!("{dir}/server.log");
+    pg_command(&[
+        "pg_ctl", "-D", &dir, "-l", &log, "-o", &options, "-w", "start",
+    ])?;
+    Some(server)
+}
+
+async fn connect(server: &Server) -> tokio_postgres::Client {
+    let (client, connection) = tokio_postgres::connect(&server.config, tokio_postgres::NoTls)
+        .await
+        .unwrap();
+    tokio::spawn(connection);
+    client
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres server"]
+async fn test_pg_store() {
+    let server = spawn_server();
+    let mut store = PgStore::open(&server.config).await.unwrap();
+
+    let mut txn = store.begin_txn().await.unwrap();
+    assert!(txn.get(b"key").await.unwrap().is_none());
+    txn.put(b"key", b"value").await.unwrap();
+    assert_eq!(txn.get(b"key").await.unwrap().unwrap(), b"value");
+    txn.commit().await.unwrap();
+
+    let mut txn = store.begin_txn().await.unwrap();
+    assert_eq!(txn.get_for_update(b"key").await.unwrap().unwrap(), b"value");
+    txn.put(b"key", b"").await.unwrap();
+    assert_eq!(txn.get(b"key").await.unwrap().unwrap(), b"");
+    txn.commit().await.unwrap();
+
+    let mut txn = store.begin_txn().await.unwrap();
+    txn.put(b"key2", b"v2").await.unwrap();
+    assert_eq!(
+        txn.scan(b"key", b"key3", 10)
+            .await
+            .unwrap()
+            .collect::<Vec<(Vec<u8>, Vec<u8>)>>(),
+        vec![
+            (b"key".to_vec(), b"".to_vec()),
+            (b"key2".to_vec(), b"v2".to_vec())
+        ]
+    );
+    assert_eq!(
+        txn.scan(b"key", b"key3", 1)
+            .await
+            .unwrap()
+            .collect::<Vec<(Vec<u8>, Vec<u8>)>>(),
+        vec![(b"key".to_vec(), b"".to_vec())]
+    );
+    txn.put(b"key4", b"v4").await.unwrap();
+    assert_eq!(
+        txn.scan_keys(b"key", b"key3", usize::MAX)
+            .await
+            .unwrap()
+            .collect::<Vec<Vec<u8>>>(),
+        vec![b"key".to_vec(), b"key2".to_vec()]
+    );
+    txn.commit().await.unwrap();
+
+    let mut txn = store.begin_txn().await.unwrap();
+    assert_eq!(txn.get(b"key").await.unwrap().unwrap(), b"");
+    txn.delete(b"key").await.unwrap();
+    assert!(txn.get(b"key").await.unwrap().is_none());
+    txn.rollback().await.unwrap();
+    assert_eq!(txn.get(b"key").await.unwrap().unwrap(), b"");
+    txn.delete(b"key").await.unwrap();
+    drop(txn);
+
+    // uncommitted writes are discarded on drop
+    let txn = store.begin_txn().await.unwrap();
+    assert_eq!(txn.get(b"key").await.unwrap().unwrap(), b"");
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres server"]
+async fn test_pg_scan_binary_keys() {
+    let server = spawn_server();
+    let mut store = PgStore::open(&server.config).await.unwrap();
+
+    let keys: Vec<&[u8]> = vec![b"k\x00", b"k\x00\x01", b"k\x01", b"k\x7f", b"k\xff"];
+    let mut txn = store.begin_txn().await.unwrap();
+    for key in keys.iter().rev() {
+        txn.put(key, key).await.unwrap();
+    }
+    txn.commit().await.unwrap();
+
+    let txn = store.begin_txn().await.unwrap();
+    assert_eq!(
+        txn.scan_keys(b"k", b"l", usize::MAX)
+            .await
+            .unwrap()
+            .collect::<Vec<_>>(),
+        keys
+    );
+    assert_eq!(
+        txn.scan(b"k\x00\x01", b"k\xff", 2)
+            .await
+            .unwrap()
+            .collect::<Vec<_>>(),
+        vec![
+            (b"k\x00\x01".to_vec(), b"k\x00\x01".to_vec()),
+            (b"k\x01".to_vec(), b"k\x01".to_vec())
+        ]
+    );
+    assert_eq!(
+        txn.scan_keys_rev(b"k", b"l", usize::MAX)
+            .await
+            .unwrap()
+            .collect::<Vec<_>>(),
+        keys.iter().rev().copied().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        txn.scan_keys_page(b"k", b"l", Some(b"k\x01"), Direction::Backward, 2)
+            .await
+            .unwrap()
+            .collect::<Vec<_>>(),
+        vec![b"k\x00\x01".to_vec(), b"k\x00".to_vec()]
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres server"]
+async fn test_pg_serialization_failure() {
+    let server = spawn_server();
+    let mut store = PgStore::open(&server.config).await.unwrap();
+    let mut txn = store.begin_txn().await.unwrap();
+    txn.put(b"count", b"1").await.unwrap();
+    txn.put(b"other", b"o").await.unwrap();
+    txn.commit().await.unwrap();
+
+    // replayed transaction continues if its reads are unaffected
+    let mut txn1 = store.begin_txn().await.unwrap();
+    let mut txn2 = store.begin_txn().await.unwrap();
+    assert_eq!(txn1.get(b"other").await.unwrap().unwrap(), b"o");
+    txn2.put(b"count", b"2").await.unwrap();
+    txn2.commit().await.unwrap();
+    txn1.put(b"count", b"3").await.unwrap();
+    txn1.commit().await.unwrap();
+
+    // and fails otherwise
+    let mut txn1 = store.begin_txn().await.unwrap();
+    let mut txn2 = store.begin_txn().await.unwrap();
+    assert_eq!(txn1.get(b"count").await.unwrap().unwrap(), b"3");
+    txn2.put(b"count", b"4").await.unwrap();
+    txn2.commit().await.unwrap();
+    assert!(matches!(
+        txn1.put(b"count", b"5").await,
+        Err(Error::TransactionConflict)
+    ));
+
+    let txn = store.begin_txn().await.unwrap();
+    assert_eq!(txn.get(b"count").await.unwrap().unwrap(), b"4");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[ignore = "needs a Postgres server"]
+async fn test_pg_concurrent_once() {
+    let server = spawn_server();
+    let mut store = PgStore::open(&server.config).await.unwrap();
+
+    // concurrent votes of the same user replace each other
+    let tasks: Vec<_> = (1..=4)
+        .map(|i| {
+            let mut store = store.clone();
+            tokio::spawn(async move {
+                let mut client = UserItemUnboundedReactionClient::<_, _, _, Vote>::new(
+                    &mut store,
+                    User(1000),
+                    Item(2000),
+                );
+                client.react(Vote(i)).await.unwrap();
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    let mut client =
+        UserItemUnboundedReactionClient::<_, _, _, Vote>::new(&mut store, User(1000), Item(2000));
+    let vote = client.get().await.unwrap().unwrap();
+    let mut client = ItemReactionClient::<_, _, Vote>::new(&mut store, Item(2000));
+    assert_eq!(
+        client.aggregate().await.unwrap(),
+        Aggregate {
+            sum: vote.0,
+            count: 1
+        }
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres server"]
+async fn test_pg_format_version() {
+    let server = spawn_server();
+    PgStore::open(&server.config).await.unwrap();
+
+    let client = connect(&server).await;
+    let value = format!(r#"{{"version":{},"cursor":null}}"#, FORMAT_VERSION + 1);
+    client
+        .execute(
+            "UPDATE rcommunity SET value = $1 WHERE key = $2",
+            &[&value.as_bytes(), &b"FormatVersionKey".as_slice()],
+        )
+        .await
+        .unwrap();
+    assert!(matches!(
+        PgStore::open(&server.config).await,
+        Err(Error::UnsupportedVersion { .. })
+    ));
+}
@@ -0,0 +1,308 @@
+//! Integration tests of [`RedisStore`] against a local `redis-server`.
+//!
+//! A server is spawned on a free port for each test, or set `REDIS_URL` to
+//! test against a running one. The tests are ignored by default, run them with
+//! `cargo test --features redis -- --ignored`. They fail if no server is
+//! available.
+#![cfg(feature = "redis")]
+
+use std::{
+    net::TcpListener,
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+use rcommunity_core::{
+    client::{ItemReactionClient, UserItemUnboundedReactionClient},
+    error::Error,
+    ops::Aggregate,
+    store::{migration::FORMAT_VERSION, redis::RedisStore, Direction, Store, Transaction},
+    ItemType, Numerical, Once, ReactionType, UserType, ID,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct User(usize);
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Item(usize);
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Vote(i64);
+
+impl UserType for User {}
+impl ID for User {}
+impl ItemType for Item {}
+impl ID for Item {}
+impl ReactionType for Vote {}
+impl Once for Vote {}
+impl Numerical for Vote {
+    type Item = i64;
+    fn value(&self) -> i64 {
+        self.0
+    }
+}
+
+struct Server {
+    url: String,
+    process: Option<Child>,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        if let Some(process) = &mut self.process {
+            let _ = process.kill();
+            let _ = process.wait();
+        }
+    }
+}
+
+async fn spawn_server() -> Server {
+    try_spawn_server()
+        .await
+        .expect("cannot start redis-server, install it or set REDIS_URL")
+}
+
+async fn try_spawn_server() -> Option<Server> {
+    if let Ok(url) = std::env::var("REDIS_URL") {
+        return Some(Server { url, process: None });
+    }
+    let port = TcpListener::bind("127.0.0.1:0")
+        .ok()?
+        .local_addr()
+        .ok()?
+        .port();
+    let process = Command::new("redis-server")
+        .args([
+            "--port",
+            &port.to_string(),
+            "--save",
+            "",
+            "--appendonly",
+            "no",
+        ])
+        .stdout(Stdio::null())
+        .spawn();
+    let process = process.ok()?;
+    let server = Server {
+        url: format!("redis://127.0.0.1:{port}/"),
+        process: Some(process),
+    };
+    let client = redis::Client::open(server.url.as_str()).ok()?;
+    for _ in 0..50 {
+        if client.get_multiplexed_async_connection().await.is_ok() {
+            return Some(server);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    None
+}
+
+/// Open a store under a namespace unique to this test.
+async fn open(server: &Server, namespace: &str) -> RedisStore {
+    let namespace = format!("{namespace}-{}", uuid::Uuid::new_v4());
+    RedisStore::open(&server.url, &namespace).await.unwrap()
+}
+
+#[tokio::test]
+#[ignore = "needs redis-server"]
+async fn test_redis_store() {
+    let server = spawn_server().await;
+    let mut store = open(&server, "store").await;
+
+    let mut txn = store.begin_txn().await.unwrap();
+    assert!(txn.get(b"key").await.unwrap().is_none());
+    txn.put(b"key", b"value").await.unwrap();
+    assert_eq!(txn.get(b"key").await.unwrap().unwrap(), b"value");
+    txn.commit().await.unwrap();
+
+    let mut txn = store.begin_txn().await.unwrap();
+    assert_eq!(txn.get_for_update(b"key").await.unwrap().unwrap(), b"value");
+    txn.put(b"key", b"").await.unwrap();
+    assert_eq!(txn.get(b"key").await.unwrap().unwrap(), b"");
+    txn.commit().await.unwrap();
+
+    let mut txn = store.begin_txn().await.unwrap();
+    txn.put(b"key2", b"v2").await.unwrap();
+    assert_eq!(
+        txn.scan(b"key", b"key3", 10)
+            .await
+            .unwrap()
+            .collect::<Vec<(Vec<u8>, Vec<u8>)>>(),
+        vec![
+            (b"key".to_vec(), b"".to_vec()),
+            (b"key2".to_vec(), b"v2".to_vec())
+        ]
+    );
+    assert_eq!(
+        txn.scan(b"key", b"key3", 1)
+            .await
+            .unwrap()
+            .collect::<Vec<(Vec<u8>, Vec<u8>)>>(),
+        vec![(b"key".to_vec(), b"".to_vec())]
+    );
+    txn.put(b"key4", b"v4").await.unwrap();
+    assert_eq!(
+        txn.scan_keys(b"key", b"key3", 10)
+            .await
+            .unwrap()
+            .collect::<Vec<Vec<u8>>>(),
+        vec![b"key".to_vec(), b"key2".to_vec()]
+    );
+    txn.commit().await.unwrap();
+
+    let mut txn = store.begin_txn().await.unwrap();
+    assert_eq!(txn.get(b"key").await.unwrap().unwrap(), b"");
+    txn.delete(b"key").await.unwrap();
+    assert!(txn.get(b"key").await.unwrap().is_none());
+    assert_eq!(
+        txn.scan_keys(b"key", b"key3", 10)
+            .await
+            .unwrap()
+            .collect::<Vec<Vec<u8>>>(),
+        vec![b"key2".to_vec()]
+    );
+    txn.commit().await.unwrap();
+
+    let txn = store.begin_txn().await.unwrap();
+    assert!(txn.get(b"key").await.unwrap().is_none());
+    assert_eq!(txn.get(b"key4").await.unwrap().unwrap(), b"v4");
+}
+
+#[tokio::test]
+#[ignore = "needs redis-server"]
+async fn test_redis_scan_binary_keys() {
+    let server = spawn_server().await;
+    let mut store = open(&server, "scan").await;
+
+    let keys: Vec<&[u8]> = vec![b"k\x00", b"k\x00\x01", b"k\x01", b"k\x7f", b"k\xff"];
+    let mut txn = store.begin_txn().await.unwrap();
+    for key in keys.iter().rev() {
+        txn.put(key, key).await.unwrap();
+    }
+    txn.commit().await.unwrap();
+
+    let txn = store.begin_txn().await.unwrap();
+    assert_eq!(
+        txn.scan_keys(b"k", b"l", usize::MAX)
+            .await
+            .unwrap()
+            .collect::<Vec<_>>(),
+        keys
+    );
+    assert_eq!(
+        txn.scan(b"k\x00\x01", b"k\xff", 2)
+            .await
+            .unwrap()
+            .collect::<Vec<_>>(),
+        vec![
+            (b"k\x00\x01".to_vec(), b"k\x00\x01".to_vec()),
+            (b"k\x01".to_vec(), b"k\x01".to_vec())
+        ]
+    );
+    assert_eq!(
+        txn.scan_keys_rev(b"k", b"l", usize::MAX)
+            .await
+            .unwrap()
+            .collect::<Vec<_>>(),
+        keys.iter().rev().copied().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        txn.scan_keys_page(b"k", b"l", Some(b"k\x01"), Direction::Backward, 2)
+            .await
+            .unwrap()
+            .collect::<Vec<_>>(),
+        vec![b"k\x00\x01".to_vec(), b"k\x00".to_vec()]
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs redis-server"]
+async fn test_redis_conflict() {
+    let server = spawn_server().await;
+    let mut store = open(&server, "conflict").await;
+
+    let mut txn1 = store.begin_txn().await.unwrap();
+    let mut txn2 = store.begin_txn().await.unwrap();
+    assert!(txn1.get_for_update(b"count").await.unwrap().is_none());
+    txn2.put(b"count", b"2").await.unwrap();
+    txn2.commit().await.unwrap();
+    txn1.put(b"count", b"1").await.unwrap();
+    assert!(matches!(
+        txn1.commit().await,
+        Err(Error::TransactionConflict)
+    ));
+
+    // writes that leave watched values unchanged are retried
+    let mut txn1 = store.begin_txn().await.unwrap();
+    let mut txn2 = store.begin_txn().await.unwrap();
+    assert_eq!(txn1.get_for_update(b"count").await.unwrap().unwrap(), b"2");
+    txn2.put(b"count", b"2").await.unwrap();
+    txn2.commit().await.unwrap();
+    txn1.put(b"count", b"3").await.unwrap();
+    txn1.commit().await.unwrap();
+
+    let mut txn = store.begin_txn().await.unwrap();
+    assert_eq!(txn.get(b"count").await.unwrap().unwrap(), b"3");
+    txn.put(b"count", b"4").await.unwrap();
+    txn.rollback().await.unwrap();
+    assert_eq!(txn.get(b"count").await.unwrap().unwrap(), b"3");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[ignore = "needs redis-server"]
+async fn test_redis_concurrent_once() {
+    let server = spawn_server().await;
+    let mut store = open(&server, "once").await;
+
+    // concurrent votes of the same user replace each other
+    let tasks: Vec<_> = (1..=4)
+        .map(|i| {
+            let mut store = store.clone();
+            tokio::spawn(async move {
+                let mut client = UserItemUnboundedReactionClient::<_, _, _, Vote>::new(
+                    &mut store,
+                    User(1000),
+                    Item(2000),
+                );
+                client.react(Vote(i)).await.unwrap();
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    let mut client =
+        UserItemUnboundedReactionClient::<_, _, _, Vote>::new(&mut store, User(1000), Item(2000));
+    let vote = client.get().await.unwrap().unwrap();
+    let mut client = ItemReactionClient::<_, _, Vote>::new(&mut store, Item(2000));
+    assert_eq!(
+        client.aggregate().await.unwrap(),
+        Aggregate {
+            sum: vote.0,
+            count: 1
+        }
+    );
+}
+
+#[tokio::test]
+#[ignore = "needs redis-server"]
+async fn test_redis_format_version() {
+    let server = spawn_server().await;
+    let namespace = format!("version-{}", uuid::Uuid::new_v4());
+    RedisStore::open(&server.url, &namespace).await.unwrap();
+
+    let client = redis::Client::open(server.url.as_str()).unwrap();
+    let mut con = client.get_multiplexed_async_connection().await.unwrap();
+    let key = format!("{namespace}:data:FormatVersionKey");
+    let value = format!(r#"{{"version":{},"cursor":null}}"#, FORMAT_VERSION + 1);
+    redis::cmd("SET")
+        .arg(key)
+        .arg(value)
+        .query_async::<_, ()>(&mut con)
+        .await
+        .unwrap();
+    assert!(matches!(
+        RedisStore::open(&server.url, &namespace).await,
+        Err(Error::UnsupportedVersion { .. })
+    ));
+}
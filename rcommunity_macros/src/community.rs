@@ -0,0 +1,465 @@
+//! Implementation of the `community!` schema macro.
+
+use std::collections::BTreeMap;
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{
+    bracketed,
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    Ident, Path, Token, Visibility,
+};
+
+/// Parsed `community!` declaration, e.g.
+/// `struct Community, Vote: [User] => [Post, Comment]`.
+struct Schema {
+    vis: Visibility,
+    name: Ident,
+    rules: Vec<Rule>,
+}
+
+/// A single `Reaction: [Users] => [Items]` rule.
+struct Rule {
+    reaction: Path,
+    users: Vec<Path>,
+    items: Vec<Path>,
+}
+
+impl Parse for Schema {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let vis: Visibility = input.parse()?;
+        input.parse::<Token![struct]>()?;
+        let name: Ident = input.parse()?;
+        let mut rules = Vec::new();
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            rules.push(input.parse()?);
+        }
+        Ok(Schema { vis, name, rules })
+    }
+}
+
+impl Parse for Rule {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let reaction: Path = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let users = parse_path_list(input)?;
+        input.parse::<Token![=>]>()?;
+        let items = parse_path_list(input)?;
+        Ok(Rule {
+            reaction,
+            users,
+            items,
+        })
+    }
+}
+
+fn parse_path_list(input: ParseStream) -> syn::Result<Vec<Path>> {
+    let content;
+    bracketed!(content in input);
+    let paths: Punctuated<Path, Token![,]> = content.parse_terminated(Path::parse)?;
+    Ok(paths.into_iter().collect())
+}
+
+/// A type declared in the schema, keyed by its name.
+struct Declared {
+    path: Path,
+    ident: Ident,
+    method: Ident,
+}
+
+impl Declared {
+    fn new(path: &Path) -> syn::Result<Self> {
+        let ident = path
+            .segments
+            .last()
+            .map(|s| s.ident.clone())
+            .ok_or_else(|| syn::Error::new_spanned(path, "expected a type name"))?;
+        let method = Ident::new(&snake_case(&ident.to_string()), ident.span());
+        Ok(Declared {
+            path: path.clone(),
+            ident,
+            method,
+        })
+    }
+}
+
+/// Convert a `CamelCase` type name to a `snake_case` method name.
+fn snake_case(name: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Insert `path` into `map` by name, rejecting different types with the same
+/// name since they would generate the same methods.
+fn declare(map: &mut BTreeMap<String, Declared>, path: &Path) -> syn::Result<String> {
+    let declared = Declared::new(path)?;
+    let key = declared.ident.to_string();
+    if let Some(existing) = map.get(&key) {
+        let existing = &existing.path;
+        if quote!(#existing).to_string() != quote!(#path).to_string() {
+            return Err(syn::Error::new_spanned(
+                path,
+                format!("`{key}` is already declared as a different type"),
+            ));
+        }
+    } else {
+        map.insert(key.clone(), declared);
+    }
+    Ok(key)
+}
+
+pub fn expand(input: TokenStream) -> syn::Result<TokenStream> {
+    let schema: Schema = syn::parse2(input)?;
+    let vis = &schema.vis;
+    let name = &schema.name;
+
+    let mut users = BTreeMap::new();
+    let mut items = BTreeMap::new();
+    let mut reactions = BTreeMap::new();
+    // user -> item -> reactions, and item -> reactions
+    let mut user_items: BTreeMap<String, BTreeMap<String, Vec<String>>> = BTreeMap::new();
+    let mut item_reactions: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for rule in &schema.rules {
+        let reaction = declare(&mut reactions, &rule.reaction)?;
+        if rule.users.is_empty() || rule.items.is_empty() {
+            return Err(syn::Error::new_spanned(
+                &rule.reaction,
+                "reaction must be declared with at least one user type and one item type",
+            ));
+        }
+        for item in &rule.items {
+            let item = declare(&mut items, item)?;
+            let received = item_reactions.entry(item).or_default();
+            if !received.contains(&reaction) {
+                received.push(reaction.clone());
+            }
+        }
+        for user in &rule.users {
+            let user = declare(&mut users, user)?;
+            for item in &rule.items {
+                let item = Declared::new(item)?.ident.to_string();
+                let given = user_items
+                    .entry(user.clone())
+                    .or_default()
+                    .entry(item)
+                    .or_default();
+                if given.contains(&reaction) {
+                    return Err(syn::Error::new_spanned(
+                        &rule.reaction,
+                        "reaction is declared more than once for the same user and item types",
+                    ));
+                }
+                given.push(reaction.clone());
+            }
+        }
+    }
+
+    // users and items share the accessor namespace of the facade
+    for (key, item) in &items {
+        if users.contains_key(key) {
+            return Err(syn::Error::new_spanned(
+                &item.path,
+                format!("`{key}` cannot be declared as both a user type and an item type"),
+            ));
+        }
+    }
+
+    let mut facade_methods = Vec::new();
+    let mut definitions = Vec::new();
+
+    for (user_key, user_items) in &user_items {
+        let user = &users[user_key];
+        let user_path = &user.path;
+        let method = &user.method;
+        let selector = format_ident!("{}{}Selector", name, user.ident);
+        let client = format_ident!("{}{}Client", name, user.ident);
+
+        facade_methods.push(quote! {
+            /// Access reactions given by users of this type.
+            #vis fn #method(&mut self) -> #selector<'_, TS> {
+                #selector { store: &mut self.store }
+            }
+        });
+
+        let mut item_methods = Vec::new();
+        for (item_key, reaction_keys) in user_items {
+            let item = &items[item_key];
+            let item_path = &item.path;
+            let item_method = &item.method;
+            let item_selector = format_ident!("{}{}{}Selector", name, user.ident, item.ident);
+            let item_client = format_ident!("{}{}{}Client", name, user.ident, item.ident);
+
+            item_methods.push(quote! {
+                /// Access reactions given by this user to items of this type.
+                #vis fn #item_method(&mut self) -> #item_selector<'_, TS> {
+                    #item_selector {
+                        store: &mut *self.store,
+                        user: ::core::clone::Clone::clone(&self.user),
+                    }
+                }
+            });
+
+            let reaction_methods = reaction_keys.iter().map(|key| {
+                let reaction = &reactions[key];
+                let reaction_path = &reaction.path;
+                let reaction_method = &reaction.method;
+                quote! {
+                    /// Access reactions of this type given by this user to this item.
+                    #vis fn #reaction_method(
+                        &mut self,
+                    ) -> ::rcommunity_core::client::UserItemUnboundedReactionClient<
+                        '_,
+                        TS,
+                        #user_path,
+                        #item_path,
+                        #reaction_path,
+                    > {
+                        ::rcommunity_core::client::UserItemUnboundedReactionClient::new(
+                            &mut *self.store,
+                            ::core::clone::Clone::clone(&self.user),
+                            ::core::clone::Clone::clone(&self.item),
+                        )
+                    }
+                }
+            });
+
+            let given_methods = reaction_keys.iter().map(|key| {
+                let reaction = &reactions[key];
+                let reaction_path = &reaction.path;
+                let reaction_method = &reaction.method;
+                quote! {
+                    /// Access reactions of this type given by this user to items of this type.
+                    #vis fn #reaction_method(
+                        &mut self,
+                    ) -> ::rcommunity_core::client::UserReactionClient<
+                        '_,
+                        TS,
+                        #user_path,
+                        #item_path,
+                        #reaction_path,
+                    > {
+                        ::rcommunity_core::client::UserReactionClient::new(
+                            &mut *self.store,
+                            ::core::clone::Clone::clone(&self.user),
+                        )
+                    }
+                }
+            });
+
+            definitions.push(quote! {
+                /// Selects an item to access reactions given by a user.
+                #vis struct #item_selector<'store, TS: ::rcommunity_core::store::Store> {
+                    store: &'store mut TS,
+                    user: #user_path,
+                }
+
+                impl<'store, TS: ::rcommunity_core::store::Store> #item_selector<'store, TS> {
+                    #(#given_methods)*
+
+                    /// Select an item by its ID.
+                    #vis fn get(self, item: impl ::core::convert::Into<#item_path>) -> #item_client<'store, TS> {
+                        #item_client {
+                            store: self.store,
+                            user: self.user,
+                            item: ::core::convert::Into::into(item),
+                        }
+                    }
+                }
+
+                /// Accesses reactions given by a user to an item.
+                #vis struct #item_client<'store, TS: ::rcommunity_core::store::Store> {
+                    store: &'store mut TS,
+                    user: #user_path,
+                    item: #item_path,
+                }
+
+                impl<'store, TS: ::rcommunity_core::store::Store> #item_client<'store, TS> {
+                    #(#reaction_methods)*
+                }
+            });
+        }
+
+        definitions.push(quote! {
+            /// Selects a user to access reactions given by the user.
+            #vis struct #selector<'store, TS: ::rcommunity_core::store::Store> {
+                store: &'store mut TS,
+            }
+
+            impl<'store, TS: ::rcommunity_core::store::Store> #selector<'store, TS> {
+                /// Select a user by its ID.
+                #vis fn get(self, user: impl ::core::convert::Into<#user_path>) -> #client<'store, TS> {
+                    #client {
+                        store: self.store,
+                        user: ::core::convert::Into::into(user),
+                    }
+                }
+            }
+
+            /// Accesses reactions given by a user.
+            #vis struct #client<'store, TS: ::rcommunity_core::store::Store> {
+                store: &'store mut TS,
+                user: #user_path,
+            }
+
+            impl<'store, TS: ::rcommunity_core::store::Store> #client<'store, TS> {
+                #(#item_methods)*
+            }
+        });
+    }
+
+    for (item_key, reaction_keys) in &item_reactions {
+        let item = &items[item_key];
+        let item_path = &item.path;
+        let method = &item.method;
+        let selector = format_ident!("{}{}Selector", name, item.ident);
+        let client = format_ident!("{}{}Client", name, item.ident);
+
+        facade_methods.push(quote! {
+            /// Access reactions received by items of this type.
+            #vis fn #method(&mut self) -> #selector<'_, TS> {
+                #selector { store: &mut self.store }
+            }
+        });
+
+        let reaction_methods = reaction_keys.iter().map(|key| {
+            let reaction = &reactions[key];
+            let reaction_path = &reaction.path;
+            let reaction_method = &reaction.method;
+            quote! {
+                /// Access reactions of this type received by this item.
+                #vis fn #reaction_method(
+                    &mut self,
+                ) -> ::rcommunity_core::client::ItemReactionClient<'_, TS, #item_path, #reaction_path> {
+                    ::rcommunity_core::client::ItemReactionClient::new(
+                        &mut *self.store,
+                        ::core::clone::Clone::clone(&self.item),
+                    )
+                }
+            }
+        });
+
+        definitions.push(quote! {
+            /// Selects an item to access reactions received by the item.
+            #vis struct #selector<'store, TS: ::rcommunity_core::store::Store> {
+                store: &'store mut TS,
+            }
+
+            impl<'store, TS: ::rcommunity_core::store::Store> #selector<'store, TS> {
+                /// Select an item by its ID.
+                #vis fn get(self, item: impl ::core::convert::Into<#item_path>) -> #client<'store, TS> {
+                    #client {
+                        store: self.store,
+                        item: ::core::convert::Into::into(item),
+                    }
+                }
+            }
+
+            /// Accesses reactions received by an item.
+            #vis struct #client<'store, TS: ::rcommunity_core::store::Store> {
+                store: &'store mut TS,
+                item: #item_path,
+            }
+
+            impl<'store, TS: ::rcommunity_core::store::Store> #client<'store, TS> {
+                #(#reaction_methods)*
+            }
+        });
+    }
+
+    // reject types not implementing the expected markers early, with errors
+    // pointing at the schema
+    let assertions = users
+        .values()
+        .map(|d| (&d.path, quote!(::rcommunity_core::UserType)))
+        .chain(
+            items
+                .values()
+                .map(|d| (&d.path, quote!(::rcommunity_core::ItemType))),
+        )
+        .chain(
+            reactions
+                .values()
+                .map(|d| (&d.path, quote!(::rcommunity_core::ReactionType))),
+        )
+        .map(|(path, bound)| {
+            let span = path
+                .segments
+                .last()
+                .map_or_else(Span::call_site, |s| s.ident.span());
+            quote::quote_spanned! {span=>
+                const _: fn() = || {
+                    fn assert_impl<T: #bound>() {}
+                    assert_impl::<#path>();
+                };
+            }
+        });
+
+    // reaction types own keyspaces, and user and item types are told apart in
+    // keys by their tags, which must thus not be shared
+    let tagged_paths = users
+        .values()
+        .chain(items.values())
+        .chain(reactions.values())
+        .map(|d| &d.path);
+
+    Ok(quote! {
+        /// Typed facade of a community, generated by `community!`.
+        #vis struct #name<TS: ::rcommunity_core::store::Store> {
+            store: TS,
+        }
+
+        impl<TS: ::rcommunity_core::store::Store> #name<TS> {
+            /// Create a community backed by `store`.
+            ///
+            /// # Panics
+            /// Will panic if types of this community share a type tag.
+            #vis fn new(store: TS) -> Self {
+                if let ::core::result::Result::Err(e) = Self::tags() {
+                    ::core::panic!("{}", e);
+                }
+                Self { store }
+            }
+
+            /// Registry of type tags of user, item and reaction types in this
+            /// community.
+            ///
+            /// # Errors
+            /// Will return `Err` if types share a type tag.
+            #vis fn tags() -> ::core::result::Result<
+                ::rcommunity_core::store::format::TagRegistry,
+                ::rcommunity_core::store::format::error::Error,
+            > {
+                let mut registry = ::rcommunity_core::store::format::TagRegistry::new();
+                #(registry.register::<#tagged_paths>()?;)*
+                ::core::result::Result::Ok(registry)
+            }
+
+            /// The backing store of this community.
+            #vis fn store(&mut self) -> &mut TS {
+                &mut self.store
+            }
+
+            #(#facade_methods)*
+        }
+
+        #(#definitions)*
+
+        #(#assertions)*
+    })
+}
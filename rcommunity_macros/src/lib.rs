@@ -1,55 +1,290 @@
 extern crate proc_macro;
 
 use proc_macro2::TokenStream;
-use quote::quote;
+use quote::{quote, quote_spanned};
+use syn::{spanned::Spanned, Data, DeriveInput, Fields, Ident, LitStr, Token, Type};
 
-#[proc_macro_derive(ID)]
-pub fn id_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    derive_id(input.into()).into()
+mod community;
+
+/// Declare a community schema and generate a typed facade for it.
+///
+/// ```ignore
+/// community!(
+///     pub struct Community,
+///     Vote: [User] => [Post, Comment],
+///     Comment: [User] => [Post],
+/// );
+/// ```
+///
+/// Each rule declares a reaction type, the user types that may give it, and the
+/// item types that may receive it. Reactions can then be accessed as
+/// `community.user().get(uid).post().get(pid).vote()`,
+/// `community.user().get(uid).post().vote()` and
+/// `community.post().get(pid).vote()`, which return the clients of
+/// `rcommunity::client`, e.g. to `.add(Vote::Upvote)`, `.get()` a once
+/// reaction, or query `.given().sum()` and `.received().sum()` of numerical
+/// reactions. Combinations not declared in the schema have no accessor and are
+/// thus rejected at compile time. See `examples/post.rs` for a complete
+/// example.
+#[proc_macro]
+pub fn community(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    community::expand(input.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
 }
 
-fn derive_id(input: TokenStream) -> TokenStream {
-    // Construct a representation of Rust code as a syntax tree
-    // that we can manipulate
-    let ast: syn::DeriveInput = syn::parse2(input).unwrap();
+/// Run a derive, turning errors into compile errors at their spans.
+fn expand(
+    input: proc_macro::TokenStream,
+    derive: fn(DeriveInput) -> syn::Result<TokenStream>,
+) -> proc_macro::TokenStream {
+    syn::parse2(input.into())
+        .and_then(derive)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
 
+/// Implement a marker trait without items for the derived type.
+fn derive_marker(ast: &DeriveInput, marker: TokenStream) -> TokenStream {
     let name = &ast.ident;
-    let gen = quote! {
-        impl ::rcommunity_core::ID for #name {}
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    quote! {
+        impl #impl_generics #marker for #name #ty_generics #where_clause {}
+    }
+}
+
+/// Implement `TypeTag` if a tag is given by `#[rcommunity(tag = "...")]`.
+fn derive_type_tag(ast: &DeriveInput) -> syn::Result<TokenStream> {
+    let mut tag: Option<LitStr> = None;
+    for attr in ast.attrs.iter().filter(|a| a.path.is_ident("rcommunity")) {
+        let value = attr.parse_args_with(|input: syn::parse::ParseStream| {
+            let key: Ident = input.parse()?;
+            if key != "tag" {
+                return Err(syn::Error::new(key.span(), "expected `tag = \"...\"`"));
+            }
+            input.parse::<Token![=]>()?;
+            input.parse::<LitStr>()
+        })?;
+        if value.value().is_empty() {
+            return Err(syn::Error::new(value.span(), "type tag cannot be empty"));
+        }
+        if tag.is_some() {
+            return Err(syn::Error::new(value.span(), "duplicate type tag"));
+        }
+        tag = Some(value);
+    }
+    let tag = match tag {
+        Some(tag) => tag,
+        None => return Ok(TokenStream::new()),
     };
-    gen
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::rcommunity_core::store::format::TypeTag for #name #ty_generics #where_clause {
+            const TAG: &'static str = #tag;
+        }
+    })
 }
 
-#[proc_macro_derive(UserType)]
+#[proc_macro_derive(ID)]
+pub fn id_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand(input, derive_id)
+}
+
+#[allow(clippy::unnecessary_wraps)]
+fn derive_id(ast: DeriveInput) -> syn::Result<TokenStream> {
+    Ok(derive_marker(&ast, quote!(::rcommunity_core::ID)))
+}
+
+/// Derive [`ID`](macro@ID) for reaction types, so that each reaction value is
+/// made at most once for each user-item pair.
+#[proc_macro_derive(Unique)]
+pub fn unique_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand(input, derive_id)
+}
+
+/// Derive `UserType`.
+///
+/// A stable tag identifying the type in store keys can be given by
+/// `#[rcommunity(tag = "...")]`, so that data is kept when the type is renamed
+/// or moved. The tag is implemented by whichever of `UserType`, `ItemType` and
+/// `ReactionType` is derived, so a tagged type can only derive one of them.
+#[proc_macro_derive(UserType, attributes(rcommunity))]
 pub fn user_type_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    derive_user(input.into()).into()
+    expand(input, derive_user)
 }
 
-fn derive_user(input: TokenStream) -> TokenStream {
-    // Construct a representation of Rust code as a syntax tree
-    // that we can manipulate
-    let ast: syn::DeriveInput = syn::parse2(input).unwrap();
+fn derive_user(ast: DeriveInput) -> syn::Result<TokenStream> {
+    let tag = derive_type_tag(&ast)?;
+    let marker = derive_marker(&ast, quote!(::rcommunity_core::UserType));
+    Ok(quote!(#marker #tag))
+}
 
-    let name = &ast.ident;
-    let gen = quote! {
-        impl ::rcommunity_core::UserType for #name {}
-    };
-    gen
+/// Derive `ItemType`, with an optional `#[rcommunity(tag = "...")]` as for
+/// [`UserType`](macro@UserType).
+#[proc_macro_derive(ItemType, attributes(rcommunity))]
+pub fn item_type_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand(input, derive_item)
+}
+
+fn derive_item(ast: DeriveInput) -> syn::Result<TokenStream> {
+    let tag = derive_type_tag(&ast)?;
+    let marker = derive_marker(&ast, quote!(::rcommunity_core::ItemType));
+    Ok(quote!(#marker #tag))
 }
 
-#[proc_macro_derive(ReactionType)]
+/// Derive `ReactionType`, with an optional `#[rcommunity(tag = "...")]` as for
+/// [`UserType`](macro@UserType).
+#[proc_macro_derive(ReactionType, attributes(rcommunity))]
 pub fn reaction_type_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    derive_reaction(input.into()).into()
+    expand(input, derive_reaction)
+}
+
+fn derive_reaction(ast: DeriveInput) -> syn::Result<TokenStream> {
+    let tag = derive_type_tag(&ast)?;
+    let marker = derive_marker(&ast, quote!(::rcommunity_core::ReactionType));
+    Ok(quote!(#marker #tag))
+}
+
+#[proc_macro_derive(Enumerable)]
+pub fn enumerable_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand(input, derive_enumerable)
+}
+
+#[allow(clippy::unnecessary_wraps)]
+fn derive_enumerable(ast: DeriveInput) -> syn::Result<TokenStream> {
+    Ok(derive_marker(&ast, quote!(::rcommunity_core::Enumerable)))
+}
+
+/// Derive `Once`. Reacting again on the same user-item pair replaces the
+/// existing reaction, unless `#[once(reject)]` is given.
+#[proc_macro_derive(Once, attributes(once))]
+pub fn once_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand(input, derive_once)
+}
+
+fn derive_once(ast: DeriveInput) -> syn::Result<TokenStream> {
+    let mut mode = None;
+    for attr in ast.attrs.iter().filter(|a| a.path.is_ident("once")) {
+        let ident: Ident = attr.parse_args()?;
+        if mode.is_some() {
+            return Err(syn::Error::new(ident.span(), "duplicate once mode"));
+        }
+        if ident == "reject" {
+            mode = Some(quote!(::rcommunity_core::OnceMode::Reject));
+        } else if ident == "replace" {
+            mode = Some(quote!(::rcommunity_core::OnceMode::Replace));
+        } else {
+            return Err(syn::Error::new(
+                ident.span(),
+                "expected `replace` or `reject`",
+            ));
+        }
+    }
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let mode = mode.map(|mode| quote!(const MODE: ::rcommunity_core::OnceMode = #mode;));
+    Ok(quote! {
+        impl #impl_generics ::rcommunity_core::Once for #name #ty_generics #where_clause {
+            #mode
+        }
+    })
+}
+
+/// Derive `Numerical`, with value type given by `#[numerical(Item = ...)]`.
+///
+/// For enums without fields, the value of each variant is its discriminant.
+/// For structs, the value is that of the first field, and the value type
+/// defaults to the one of the first field.
+#[proc_macro_derive(Numerical, attributes(numerical))]
+pub fn numerical_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand(input, derive_numerical)
 }
 
-fn derive_reaction(input: TokenStream) -> TokenStream {
-    // Construct a representation of Rust code as a syntax tree
-    // that we can manipulate
-    let ast: syn::DeriveInput = syn::parse2(input).unwrap();
+fn derive_numerical(ast: DeriveInput) -> syn::Result<TokenStream> {
+    let mut item_type = None;
+    for attr in ast.attrs.iter().filter(|a| a.path.is_ident("numerical")) {
+        let ty = attr.parse_args_with(|input: syn::parse::ParseStream| {
+            let key: Ident = input.parse()?;
+            if key != "Item" {
+                return Err(syn::Error::new(key.span(), "expected `Item = <type>`"));
+            }
+            input.parse::<Token![=]>()?;
+            input.parse::<Type>()
+        })?;
+        if item_type.is_some() {
+            return Err(syn::Error::new_spanned(
+                ty,
+                "duplicate numerical value type",
+            ));
+        }
+        item_type = Some(ty);
+    }
 
     let name = &ast.ident;
-    let gen = quote! {
-        impl ::rcommunity_core::ReactionType for #name {}
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let (item_type, value) = match &ast.data {
+        Data::Enum(data) => {
+            let item_type = item_type.ok_or_else(|| {
+                syn::Error::new(
+                    name.span(),
+                    "missing `#[numerical(Item = <type>)]` for enum",
+                )
+            })?;
+            let mut arms = Vec::new();
+            for variant in &data.variants {
+                if !matches!(variant.fields, Fields::Unit) {
+                    return Err(syn::Error::new(
+                        variant.fields.span(),
+                        "numerical enum variants cannot have fields",
+                    ));
+                }
+                let ident = &variant.ident;
+                arms.push(quote_spanned! {variant.span()=>
+                    Self::#ident => Self::#ident as #item_type,
+                });
+            }
+            let value = quote! {
+                match self {
+                    #(#arms)*
+                }
+            };
+            (quote!(#item_type), value)
+        }
+        Data::Struct(data) => {
+            let field = data.fields.iter().next().ok_or_else(|| {
+                syn::Error::new(
+                    name.span(),
+                    "numerical structs must have at least one field",
+                )
+            })?;
+            let ty = &field.ty;
+            let member = field
+                .ident
+                .as_ref()
+                .map_or_else(|| quote!(0), |ident| quote!(#ident));
+            let item_type = item_type.map_or_else(
+                || quote!(<#ty as ::rcommunity_core::Numerical>::Item),
+                |t| quote!(#t),
+            );
+            let value = quote_spanned! {ty.span()=>
+                ::core::convert::Into::into(::rcommunity_core::Numerical::value(&self.#member))
+            };
+            (item_type, value)
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new(
+                data.union_token.span,
+                "numerical unions are not supported",
+            ))
+        }
     };
-    gen
+    Ok(quote! {
+        impl #impl_generics ::rcommunity_core::Numerical for #name #ty_generics #where_clause {
+            type Item = #item_type;
+            fn value(&self) -> Self::Item {
+                #value
+            }
+        }
+    })
 }